@@ -1,38 +1,163 @@
+mod acme;
+mod cache;
 mod cddns;
+mod client_ip;
+mod dyndns2;
 mod health;
+mod limits;
+mod reload;
+mod tls;
 
-use std::{error::Error, sync::Arc};
+use std::{error::Error, net::SocketAddr, sync::Arc, time::Duration};
 
-use axum::{routing::get, Router};
-use tokio::signal;
+use arc_swap::ArcSwap;
+use axum::{middleware, routing::get, Router};
+use axum_server::Handle;
+use tokio::{net::TcpListener, signal, sync::watch};
 use tower_http::trace::{self, TraceLayer};
 use tracing::Level;
 
 use crate::config::AppConfig;
+use cache::RecordCache;
+use limits::Limiter;
+
+/// Configuration swapped on reload
+pub(crate) type SharedConfig = Arc<ArcSwap<AppConfig>>;
+
+/// State shared by the handlers
+#[derive(Clone)]
+pub(crate) struct AppState {
+    pub(crate) config: SharedConfig,
+    pub(crate) records: Arc<RecordCache>,
+    pub(crate) limits: Arc<Limiter>,
+}
 
 pub(crate) async fn run_app() -> Result<(), Box<dyn Error>> {
     observability::init();
 
-    let shared_config = Arc::new(AppConfig::build()?);
+    let shared_config: SharedConfig = Arc::new(ArcSwap::from_pointee(AppConfig::build()?));
+    // listeners, tls, cache and limits are only read at startup
+    let startup_config = shared_config.load_full();
+    let state = AppState {
+        config: Arc::clone(&shared_config),
+        records: Arc::new(RecordCache::build(&startup_config.cache)?),
+        limits: Arc::new(Limiter::build(&startup_config.limits)),
+    };
+
+    tokio::spawn(reload::on_signal(Arc::clone(&shared_config)));
+
+    if startup_config.reload_on_change {
+        tokio::spawn(reload::on_change(Arc::clone(&shared_config)));
+    }
 
     let app = Router::new()
-        .route("/health", get(health::health))
         .route(
             "/gandi/:fqdn/:rrset_name/:rrset_type/:rrset_value",
             get(cddns::gandi),
         )
-        .with_state(Arc::clone(&shared_config))
+        .route(
+            "/gandi/:fqdn/:rrset_name/:rrset_type",
+            get(cddns::gandi_client_ip),
+        )
+        .route("/nic/update", get(dyndns2::nic_update))
+        .route_layer(middleware::from_fn_with_state(state.clone(), limits::limit))
+        .route("/health", get(health::health))
+        .with_state(state)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
         );
 
-    let listener = tokio::net::TcpListener::bind(&shared_config.listen).await?;
+    let handle = Handle::new();
+    let (shutdown, stopped) = watch::channel(());
+
+    tokio::spawn({
+        let handle = handle.clone();
+
+        async move {
+            shutdown_signal().await;
+            _ = shutdown.send(());
+            handle.graceful_shutdown(Some(Duration::from_secs(10)));
+        }
+    });
+
+    tokio::try_join!(
+        serve_http(startup_config.listen.as_deref(), app.clone(), stopped),
+        serve_https(&startup_config, shared_config, app, handle),
+    )?;
+
+    Ok(())
+}
+
+async fn serve_http(
+    listen: Option<&str>,
+    app: Router,
+    mut stopped: watch::Receiver<()>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(listen) = listen else {
+        return Ok(());
+    };
+
+    let listener = TcpListener::bind(listen).await?;
+
+    tracing::info!("listening on http://{}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        _ = stopped.changed().await;
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn serve_https(
+    startup_config: &AppConfig,
+    shared_config: SharedConfig,
+    app: Router,
+    handle: Handle,
+) -> Result<(), Box<dyn Error>> {
+    let Some(tls) = &startup_config.tls else {
+        return Ok(());
+    };
+
+    // the current certificate is used until the next renewal if it can't be renewed
+    if let Some(acme) = &tls.acme {
+        if let Err(e) = acme::ensure(acme, tls, &acme::Gandi::new(Arc::clone(&shared_config))).await
+        {
+            match tls
+                .certificate
+                .as_ref()
+                .is_some_and(|certificate| certificate.exists())
+            {
+                true => tracing::error!("acme: certificate not renewed: {}", e),
+                false => return Err(format!("acme: {}", e).into()),
+            }
+        }
+    }
+
+    let config = tls::rustls_config(tls)
+        .await
+        .map_err(|e| format!("tls: {}", e))?;
+    let listener = TcpListener::bind(&tls.listen).await?.into_std()?;
+
+    match &tls.acme {
+        Some(acme) => tokio::spawn(acme::renew(
+            acme.clone(),
+            tls.clone(),
+            config.clone(),
+            acme::Gandi::new(Arc::clone(&shared_config)),
+        )),
+        None => tokio::spawn(tls::watch(config.clone(), tls.clone())),
+    };
 
-    tracing::info!("listening on {}", listener.local_addr()?);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
+    tracing::info!("listening on https://{}", listener.local_addr()?);
+    axum_server::from_tcp_rustls(listener, config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await?;
 
     Ok(())
@@ -0,0 +1,811 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum_server::tls_rustls::RustlsConfig;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use gandi_v5_livedns_api::{
+    records::{add_values, remove_values, Record, UpsertRecord},
+    Api, Endpoint,
+};
+use rcgen::{CertificateParams, KeyPair, PKCS_ECDSA_P256_SHA256};
+use reqwest::{header, Client, Response};
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{self, EcdsaKeyPair, KeyPair as _},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use super::SharedConfig;
+use crate::config::{AcmeConfig, TlsConfig};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+const CHALLENGE_TTL: u32 = 300;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 30;
+/// Delay between two checks of the expiration of the certificate
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+/// Delay before a new attempt after a failed renewal
+const RETRY_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Records of the DNS-01 challenges (Gandi, or a mock in tests)
+pub(crate) trait Dns {
+    /// Add values to the `_acme-challenge` TXT record of a domain
+    ///
+    /// The other values are kept, they can be used by another ACME client at the same time.
+    async fn create_challenge(&self, domain: &str, values: &[String]) -> Result<()>;
+    /// Remove values from the `_acme-challenge` TXT record of a domain, deleted when no value remains
+    async fn delete_challenge(&self, domain: &str, values: &[String]) -> Result<()>;
+}
+
+/// Challenge records created with the personal access token of the whitelisted domain
+pub(crate) struct Gandi {
+    config: SharedConfig,
+}
+
+impl Gandi {
+    pub(crate) fn new(config: SharedConfig) -> Self {
+        Gandi { config }
+    }
+
+    /// Api, domain and rrset name of the challenge record of a domain
+    fn challenge(&self, domain: &str) -> Result<(Api, String, String)> {
+        let config = self.config.load();
+        let (fqdn, rrset_name) = config
+            .split_hostname(domain)
+            .ok_or(format!("domain {} is not in the whitelist", domain))?;
+        let token = config
+            .personal_access_token(&fqdn)
+            .ok_or(format!("no personal access token for {}", fqdn))?;
+        let api = Api::build(Endpoint::Prod, token.expose()).map_err(|e| e.to_string())?;
+
+        Ok((api, fqdn, challenge_name(&rrset_name)))
+    }
+
+    /// Current challenge record
+    async fn current(api: &Api, fqdn: &str, rrset_name: &str) -> Result<Option<Record>> {
+        Ok(api
+            .find_record_by_name_and_type(fqdn, rrset_name, "TXT")
+            .await
+            .map_err(|e| format!("{}.{} TXT: {}", rrset_name, fqdn, e))?)
+    }
+}
+
+impl Dns for Gandi {
+    async fn create_challenge(&self, domain: &str, values: &[String]) -> Result<()> {
+        let (api, fqdn, rrset_name) = self.challenge(domain)?;
+        let current = Gandi::current(&api, &fqdn, &rrset_name)
+            .await?
+            .map(|record| record.rrset_values)
+            .unwrap_or_default();
+        let record = UpsertRecord {
+            rrset_values: add_values(&current, values),
+            rrset_ttl: Some(CHALLENGE_TTL),
+        };
+
+        api.upsert_record_by_name_and_type(&fqdn, &rrset_name, "TXT", &record)
+            .await
+            .map_err(|e| format!("{}.{} TXT: {}", rrset_name, fqdn, e))?;
+
+        Ok(())
+    }
+
+    async fn delete_challenge(&self, domain: &str, values: &[String]) -> Result<()> {
+        let (api, fqdn, rrset_name) = self.challenge(domain)?;
+        let Some(current) = Gandi::current(&api, &fqdn, &rrset_name).await? else {
+            return Ok(());
+        };
+        let remaining = remove_values(&current.rrset_values, values);
+
+        let removed = match remaining.is_empty() {
+            true => api
+                .delete_record_by_name_and_type(&fqdn, &rrset_name, "TXT")
+                .await
+                .map_err(|e| e.to_string()),
+            false => {
+                let record = UpsertRecord {
+                    rrset_values: remaining,
+                    rrset_ttl: current.rrset_ttl,
+                };
+
+                api.upsert_record_by_name_and_type(&fqdn, &rrset_name, "TXT", &record)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+        };
+
+        removed.map_err(|e| format!("{}.{} TXT: {}", rrset_name, fqdn, e))?;
+
+        Ok(())
+    }
+}
+
+/// Name of the challenge record of a rrset ('@' for the domain itself)
+fn challenge_name(rrset_name: &str) -> String {
+    match rrset_name {
+        "@" => "_acme-challenge".to_owned(),
+        rrset_name => format!("_acme-challenge.{}", rrset_name),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: String,
+}
+
+/// Error document of an ACME server (RFC 7807)
+#[derive(Deserialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    kind: String,
+    detail: Option<String>,
+}
+
+/// Key of the ACME account, signing the requests (ES256)
+struct AccountKey {
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl AccountKey {
+    /// Read the key, or generate and store it if missing
+    fn load(path: &Path) -> Result<Self> {
+        let key = match path.exists() {
+            true => KeyPair::from_pem(&fs::read_to_string(path)?)?,
+            false => {
+                let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)?;
+                write_private(path, key.serialize_pem().as_bytes())?;
+                tracing::info!("acme: account key generated ({})", path.display());
+                key
+            }
+        };
+
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            &key.serialize_der(),
+            &rng,
+        )
+        .map_err(|e| format!("account key {}: {}", path.display(), e))?;
+
+        Ok(AccountKey { key, rng })
+    }
+
+    /// Public key (RFC 7517)
+    fn jwk(&self) -> Value {
+        // uncompressed point: 0x04 | x | y
+        let point = self.key.public_key().as_ref();
+
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    /// Thumbprint of the public key (RFC 7638)
+    fn thumbprint(&self) -> String {
+        let jwk = self.jwk();
+        // members in lexicographic order, without whitespace
+        let canonical = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            jwk["x"].as_str().unwrap_or_default(),
+            jwk["y"].as_str().unwrap_or_default()
+        );
+
+        URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, canonical.as_bytes()))
+    }
+
+    /// Flattened JWS of a payload ("" for a POST-as-GET)
+    fn sign(&self, protected: &Value, payload: &str) -> Result<Value> {
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let signature = self
+            .key
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|e| format!("signature: {}", e))?;
+
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        }))
+    }
+}
+
+/// Value of the TXT record of a DNS-01 challenge
+fn dns01_value(token: &str, thumbprint: &str) -> String {
+    let authorization = format!("{}.{}", token, thumbprint);
+
+    URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, authorization.as_bytes()))
+}
+
+/// Client of an ACME server (RFC 8555)
+struct Acme {
+    http: Client,
+    directory: Directory,
+    key: AccountKey,
+    /// Url of the account
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl Acme {
+    async fn connect(config: &AcmeConfig) -> Result<Self> {
+        let mut http = Client::builder();
+
+        if let Some(ca_certificate) = &config.ca_certificate {
+            http = http
+                .add_root_certificate(reqwest::Certificate::from_pem(&fs::read(ca_certificate)?)?);
+        }
+
+        let http = http.build()?;
+        let directory = http
+            .get(&config.directory_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut acme = Acme {
+            http,
+            directory,
+            key: AccountKey::load(&config.account_key)?,
+            kid: None,
+            nonce: None,
+        };
+
+        let payload = json!({
+            "termsOfServiceAgreed": true,
+            "contact": config.contact,
+        });
+        let url = acme.directory.new_account.to_owned();
+        let response = acme.post(&url, Some(&payload)).await?;
+
+        acme.kid = Some(location(&response)?);
+
+        Ok(acme)
+    }
+
+    async fn nonce(&mut self) -> Result<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+
+        let response = self.http.head(&self.directory.new_nonce).send().await?;
+
+        replay_nonce(&response).ok_or("no nonce from the ACME server".into())
+    }
+
+    /// Signed request, with a payload or as GET (RFC 8555 6.3)
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<Response> {
+        let payload = payload
+            .map(|payload| URL_SAFE_NO_PAD.encode(payload.to_string()))
+            .unwrap_or_default();
+
+        // a bad nonce is retried once with the nonce of the error
+        for attempt in 0..2 {
+            let mut protected = json!({
+                "alg": "ES256",
+                "nonce": self.nonce().await?,
+                "url": url,
+            });
+
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.key.jwk(),
+            }
+
+            let response = self
+                .http
+                .post(url)
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                .body(self.key.sign(&protected, &payload)?.to_string())
+                .send()
+                .await?;
+
+            self.nonce = replay_nonce(&response);
+
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let status = response.status();
+            let problem: Problem = response.json().await.unwrap_or(Problem {
+                kind: String::new(),
+                detail: None,
+            });
+
+            if problem.kind != "urn:ietf:params:acme:error:badNonce" || attempt > 0 {
+                return Err(format!(
+                    "{} {} {}",
+                    status,
+                    problem.kind,
+                    problem.detail.unwrap_or_default()
+                )
+                .into());
+            }
+        }
+
+        unreachable!("the last attempt returns")
+    }
+
+    async fn get<T: DeserializeOwned>(&mut self, url: &str) -> Result<T> {
+        Ok(self.post(url, None).await?.json().await?)
+    }
+
+    /// Order a certificate of the domains, returns the certificate chain and its key (PEM)
+    async fn order(&mut self, config: &AcmeConfig, dns: &impl Dns) -> Result<(String, String)> {
+        let identifiers: Vec<Value> = config
+            .domains
+            .iter()
+            .map(|domain| json!({ "type": "dns", "value": domain }))
+            .collect();
+        let url = self.directory.new_order.to_owned();
+        let response = self
+            .post(&url, Some(&json!({ "identifiers": identifiers })))
+            .await?;
+        let order_url = location(&response)?;
+        let order: Order = response.json().await?;
+
+        // challenge records by domain (a wildcard and its domain share a record)
+        let mut records: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut challenges = vec![];
+        let thumbprint = self.key.thumbprint();
+
+        for url in &order.authorizations {
+            let authorization: Authorization = self.get(url).await?;
+
+            if authorization.status == "valid" {
+                continue;
+            }
+
+            let challenge = authorization
+                .challenges
+                .iter()
+                .find(|challenge| challenge.kind == "dns-01")
+                .ok_or(format!(
+                    "no dns-01 challenge for {}",
+                    authorization.identifier.value
+                ))?;
+
+            records
+                .entry(authorization.identifier.value.to_owned())
+                .or_default()
+                .push(dns01_value(&challenge.token, &thumbprint));
+            challenges.push((url.to_owned(), challenge.url.to_owned()));
+        }
+
+        let mut created = vec![];
+        let mut validated = Ok(());
+
+        for (domain, values) in &records {
+            match dns.create_challenge(domain, values).await {
+                Ok(()) => created.push((domain, values)),
+                Err(e) => {
+                    validated = Err(e);
+                    break;
+                }
+            }
+        }
+
+        if validated.is_ok() && !challenges.is_empty() {
+            tokio::time::sleep(Duration::from_secs(config.propagation_delay)).await;
+            validated = self.validate(&challenges).await;
+        }
+
+        for (domain, values) in created {
+            if let Err(e) = dns.delete_challenge(domain, values).await {
+                tracing::error!("acme: challenge record of {} not deleted: {}", domain, e);
+            }
+        }
+
+        validated?;
+
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)?;
+        let csr = CertificateParams::new(config.domains.to_owned())?.serialize_request(&key)?;
+
+        if order.status != "valid" {
+            self.post(
+                &order.finalize,
+                Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(csr.der()) })),
+            )
+            .await?;
+        }
+
+        let order: Order = self.poll(&order_url, "order").await?;
+        let certificate = order
+            .certificate
+            .ok_or("no certificate in the valid order")?;
+        let chain = self.post(&certificate, None).await?.text().await?;
+
+        Ok((chain, key.serialize_pem()))
+    }
+
+    /// Respond to the challenges and wait for the validation of the authorizations
+    async fn validate(&mut self, challenges: &[(String, String)]) -> Result<()> {
+        for (_, challenge) in challenges {
+            self.post(challenge, Some(&json!({}))).await?;
+        }
+
+        for (authorization, _) in challenges {
+            let _: Value = self.poll(authorization, "authorization").await?;
+        }
+
+        Ok(())
+    }
+
+    /// Poll an order or an authorization until it's valid
+    async fn poll<T: DeserializeOwned>(&mut self, url: &str, what: &str) -> Result<T> {
+        for _ in 0..POLL_ATTEMPTS {
+            let response: Value = self.get(url).await?;
+
+            match response["status"].as_str() {
+                Some("valid") => return Ok(serde_json::from_value(response)?),
+                Some("invalid") => {
+                    return Err(format!("{} {} is invalid: {}", what, url, response).into())
+                }
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+
+        Err(format!(
+            "{} {} is not valid after {} attempts",
+            what, url, POLL_ATTEMPTS
+        )
+        .into())
+    }
+}
+
+fn replay_nonce(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get("replay-nonce")
+        .and_then(|nonce| nonce.to_str().ok())
+        .map(str::to_owned)
+}
+
+fn location(response: &Response) -> Result<String> {
+    Ok(response
+        .headers()
+        .get(header::LOCATION)
+        .ok_or("no location from the ACME server")?
+        .to_str()?
+        .to_owned())
+}
+
+/// Expiration of the first certificate of a PEM chain
+fn not_after(chain: &[u8]) -> Result<SystemTime> {
+    let (_, pem) = x509_parser::pem::parse_x509_pem(chain)?;
+    let certificate = pem.parse_x509()?;
+    let timestamp = certificate.validity().not_after.timestamp();
+
+    Ok(UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64))
+}
+
+/// Delay before the renewal of the certificate, zero if it's missing or invalid
+fn renewal_in(certificate: &Path, renew_before: Duration) -> Duration {
+    fs::read(certificate)
+        .ok()
+        .and_then(|chain| not_after(&chain).ok())
+        .and_then(|not_after| not_after.checked_sub(renew_before))
+        .and_then(|renewal| renewal.duration_since(SystemTime::now()).ok())
+        .unwrap_or_default()
+}
+
+/// Path of the file written before replacing a file (`{path}.tmp`)
+fn temporary(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    io::Write::write_all(&mut options.open(path)?, contents)
+}
+
+/// Obtain a certificate when it's missing or expires within the renewal period,
+/// returns true if a new certificate is stored
+pub(crate) async fn ensure(acme: &AcmeConfig, tls: &TlsConfig, dns: &impl Dns) -> Result<bool> {
+    let (Some(certificate), Some(key)) = (&tls.certificate, &tls.key) else {
+        return Err("acme requires a certificate and a key file".into());
+    };
+
+    let renew_before = Duration::from_secs(acme.renew_before * 86400);
+
+    if !renewal_in(certificate, renew_before).is_zero() {
+        return Ok(false);
+    }
+
+    tracing::info!(
+        "acme: ordering a certificate for {}",
+        acme.domains.join(",")
+    );
+
+    let (chain, private_key) = Acme::connect(acme).await?.order(acme, dns).await?;
+
+    // the key and the certificate are replaced once both are written, so they always match
+    let (key_tmp, certificate_tmp) = (temporary(key), temporary(certificate));
+
+    if let Err(e) = write_private(&key_tmp, private_key.as_bytes())
+        .and_then(|()| fs::write(&certificate_tmp, &chain))
+    {
+        _ = fs::remove_file(&key_tmp);
+        _ = fs::remove_file(&certificate_tmp);
+        return Err(e.into());
+    }
+
+    fs::rename(&key_tmp, key)?;
+    fs::rename(&certificate_tmp, certificate)?;
+
+    let valid = not_after(chain.as_bytes())?
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    tracing::info!(
+        "acme: certificate stored, valid for {} days",
+        valid.as_secs() / 86400
+    );
+
+    Ok(true)
+}
+
+/// Renew the certificate before its expiration and swap it in the HTTPS listener
+pub(crate) async fn renew(acme: AcmeConfig, tls: TlsConfig, config: RustlsConfig, dns: Gandi) {
+    let renew_before = Duration::from_secs(acme.renew_before * 86400);
+
+    loop {
+        let delay = match &tls.certificate {
+            Some(certificate) => renewal_in(certificate, renew_before).min(CHECK_INTERVAL),
+            None => return,
+        };
+
+        tokio::time::sleep(delay).await;
+
+        let renewed = match ensure(&acme, &tls, &dns).await {
+            Ok(renewed) => renewed,
+            Err(e) => {
+                tracing::error!("acme: certificate not renewed: {}", e);
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+
+        if let (true, Some(certificate), Some(key)) = (renewed, &tls.certificate, &tls.key) {
+            match config.reload_from_pem_file(certificate, key).await {
+                Ok(()) => tracing::info!("tls: certificate reloaded"),
+                Err(e) => tracing::error!("tls: certificate not reloaded: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, sync::Mutex};
+
+    /// Challenge records kept in memory
+    #[derive(Default)]
+    struct MockDns {
+        records: Mutex<BTreeMap<String, Vec<String>>>,
+        /// Values of the records during the validation
+        validated: Mutex<BTreeMap<String, Vec<String>>>,
+        deleted: Mutex<Vec<String>>,
+    }
+
+    impl Dns for MockDns {
+        async fn create_challenge(&self, domain: &str, values: &[String]) -> Result<()> {
+            let mut records = self.records.lock().unwrap();
+            let values = add_values(records.get(domain).map_or(&[], Vec::as_slice), values);
+
+            records.insert(domain.to_owned(), values.to_owned());
+            self.validated
+                .lock()
+                .unwrap()
+                .insert(domain.to_owned(), values);
+            Ok(())
+        }
+
+        async fn delete_challenge(&self, domain: &str, values: &[String]) -> Result<()> {
+            let mut records = self.records.lock().unwrap();
+            let Some(current) = records.get(domain) else {
+                return Ok(());
+            };
+            let remaining = remove_values(current, values);
+
+            match remaining.is_empty() {
+                true => {
+                    records.remove(domain);
+                    self.deleted.lock().unwrap().push(domain.to_owned());
+                }
+                false => {
+                    records.insert(domain.to_owned(), remaining);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn account_key() -> AccountKey {
+        let path = env::temp_dir().join(format!("custom-ddns-account-{}.pem", std::process::id()));
+        let key = AccountKey::load(&path).unwrap();
+
+        // the stored key is read again
+        let reloaded = AccountKey::load(&path).unwrap();
+        assert_eq!(key.jwk(), reloaded.jwk());
+
+        fs::remove_file(path).unwrap();
+        key
+    }
+
+    #[test]
+    fn challenge_names() {
+        assert_eq!(challenge_name("@"), "_acme-challenge");
+        assert_eq!(challenge_name("ddns"), "_acme-challenge.ddns");
+    }
+
+    #[test]
+    fn dns01() {
+        // sha256 of "token.thumbprint"
+        assert_eq!(
+            dns01_value("token", "thumbprint"),
+            "61rBZ_4knHblO0MNoxFsXZ_eTFUHum0B6IVRbhvUn5I"
+        );
+    }
+
+    #[test]
+    fn signed_requests() {
+        let key = account_key();
+        let protected = json!({ "alg": "ES256", "nonce": "nonce", "url": "https://acme/order" });
+        let jws = key.sign(&protected, "e30").unwrap();
+
+        let signed = format!("{}.e30", jws["protected"].as_str().unwrap());
+        let signature = URL_SAFE_NO_PAD
+            .decode(jws["signature"].as_str().unwrap())
+            .unwrap();
+
+        assert!(signature::UnparsedPublicKey::new(
+            &signature::ECDSA_P256_SHA256_FIXED,
+            key.key.public_key().as_ref()
+        )
+        .verify(signed.as_bytes(), &signature)
+        .is_ok());
+        assert_eq!(key.thumbprint().len(), 43);
+    }
+
+    #[test]
+    fn renewal() {
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).unwrap();
+        let mut params = CertificateParams::new(vec!["ddns.example.org".to_owned()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2100, 1, 1);
+        let certificate = params.self_signed(&key).unwrap();
+
+        let path = env::temp_dir().join(format!("custom-ddns-cert-{}.pem", std::process::id()));
+        fs::write(&path, certificate.pem()).unwrap();
+
+        assert_eq!(
+            not_after(certificate.pem().as_bytes()).unwrap(),
+            UNIX_EPOCH + Duration::from_secs(4102444800)
+        );
+        assert!(!renewal_in(&path, Duration::from_secs(30 * 86400)).is_zero());
+        assert!(renewal_in(&path, Duration::from_secs(100 * 365 * 86400)).is_zero());
+        assert!(renewal_in(Path::new("missing.pem"), Duration::ZERO).is_zero());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    /// Certificate of a local Pebble server (<https://github.com/letsencrypt/pebble>)
+    /// validating every challenge, the Gandi side is mocked:
+    ///
+    /// ```bash
+    /// PEBBLE_VA_ALWAYS_VALID=1 pebble -config test/config/pebble-config.json
+    /// PEBBLE_DIRECTORY=https://localhost:14000/dir PEBBLE_CA=test/certs/pebble.minica.pem \
+    ///     cargo test -p custom-ddns -- --ignored pebble
+    /// ```
+    #[tokio::test]
+    #[ignore]
+    async fn pebble() {
+        let directory = env::temp_dir().join(format!("custom-ddns-pebble-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let acme = AcmeConfig {
+            directory_url: env::var("PEBBLE_DIRECTORY")
+                .unwrap_or("https://localhost:14000/dir".to_owned()),
+            contact: vec!["mailto:admin@example.org".to_owned()],
+            domains: vec!["example.org".to_owned(), "*.example.org".to_owned()],
+            account_key: directory.join("account.pem"),
+            renew_before: 30,
+            propagation_delay: 0,
+            ca_certificate: env::var("PEBBLE_CA").ok().map(Into::into),
+        };
+        let tls = TlsConfig {
+            listen: "127.0.0.1:3443".to_owned(),
+            certificate: Some(directory.join("certificate.pem")),
+            key: Some(directory.join("key.pem")),
+            pkcs12: None,
+            pkcs12_password: String::new(),
+            reload_interval: 60,
+            acme: Some(acme.clone()),
+        };
+        let dns = MockDns::default();
+
+        assert!(ensure(&acme, &tls, &dns).await.unwrap());
+        // a single record for the domain and its wildcard, deleted after the validation
+        assert_eq!(dns.validated.lock().unwrap()["example.org"].len(), 2);
+        assert_eq!(*dns.deleted.lock().unwrap(), vec!["example.org"]);
+        assert!(dns.records.lock().unwrap().is_empty());
+
+        // the certificate isn't renewed before the renewal period
+        assert!(!ensure(&acme, &tls, &dns).await.unwrap());
+        assert!(RustlsConfig::from_pem_file(
+            tls.certificate.as_ref().unwrap(),
+            tls.key.as_ref().unwrap()
+        )
+        .await
+        .is_ok());
+
+        // the value of another ACME client is kept during and after a new order
+        fs::remove_file(tls.certificate.as_ref().unwrap()).unwrap();
+        let dns = MockDns::default();
+        dns.records
+            .lock()
+            .unwrap()
+            .insert("example.org".to_owned(), vec!["other-client".to_owned()]);
+
+        assert!(ensure(&acme, &tls, &dns).await.unwrap());
+        assert_eq!(dns.validated.lock().unwrap()["example.org"].len(), 3);
+        assert_eq!(
+            dns.records.lock().unwrap()["example.org"],
+            vec!["other-client"]
+        );
+        assert!(dns.deleted.lock().unwrap().is_empty());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
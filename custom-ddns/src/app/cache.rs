@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use gandi_v5_livedns_api::Api;
+use serde::{Deserialize, Serialize};
+
+use crate::config::CacheConfig;
+
+/// Identity of a record: fqdn, rrset_name and rrset_type
+type Key = (String, String, String);
+
+/// Last known values of a record
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct CachedRecord {
+    fqdn: String,
+    rrset_name: String,
+    rrset_type: String,
+    rrset_values: Vec<String>,
+    rrset_ttl: Option<u32>,
+    /// When the values were read from or sent to Gandi (seconds since the epoch)
+    checked: u64,
+}
+
+impl CachedRecord {
+    fn key(&self) -> Key {
+        (
+            self.fqdn.to_owned(),
+            self.rrset_name.to_owned(),
+            self.rrset_type.to_owned(),
+        )
+    }
+
+    fn same(&self, rrset_values: &[String], rrset_ttl: u32) -> bool {
+        let mut cached = self.rrset_values.to_owned();
+        let mut values = rrset_values.to_owned();
+        cached.sort();
+        values.sort();
+
+        cached == values && self.rrset_ttl == Some(rrset_ttl)
+    }
+}
+
+/// Values of the records updated by the service, to not send an update when nothing changes
+///
+/// Records are read again from Gandi after the refresh interval (eg: changed from the console).
+pub(crate) struct RecordCache {
+    records: Mutex<HashMap<Key, CachedRecord>>,
+    /// Optional file keeping the cache across restarts
+    state_file: Option<PathBuf>,
+    /// Held while the state file is written
+    writing: tokio::sync::Mutex<()>,
+    refresh_interval: Duration,
+}
+
+impl RecordCache {
+    pub(crate) fn build(config: &CacheConfig) -> Result<Self, String> {
+        let records: Vec<CachedRecord> = match &config.state_file {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|state| serde_yaml::from_str(&state).map_err(|e| e.to_string()))
+                .map_err(|e| format!("cache: {} ({})", e, path.display()))?,
+            _ => vec![],
+        };
+
+        Ok(RecordCache {
+            records: Mutex::new(
+                records
+                    .into_iter()
+                    .map(|record| (record.key(), record))
+                    .collect(),
+            ),
+            state_file: config.state_file.to_owned(),
+            writing: tokio::sync::Mutex::new(()),
+            refresh_interval: Duration::from_secs(config.refresh_interval),
+        })
+    }
+
+    /// Returns true if the record already has the values and the ttl
+    ///
+    /// The record is read from Gandi when it isn't cached or the cache is older than the refresh interval.
+    pub(crate) async fn unchanged(
+        &self,
+        api: &Api,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+        rrset_values: &[String],
+        rrset_ttl: u32,
+    ) -> Result<bool, String> {
+        let key = (
+            fqdn.to_owned(),
+            rrset_name.to_owned(),
+            rrset_type.to_owned(),
+        );
+
+        if let Some(cached) = self.fresh(&key) {
+            return Ok(cached.same(rrset_values, rrset_ttl));
+        }
+
+        let current = api
+            .find_record_by_name_and_type(fqdn, rrset_name, rrset_type)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match current {
+            Some(current) => {
+                self.set(
+                    fqdn,
+                    rrset_name,
+                    rrset_type,
+                    &current.rrset_values,
+                    current.rrset_ttl,
+                )
+                .await;
+
+                Ok(self
+                    .fresh(&key)
+                    .is_some_and(|cached| cached.same(rrset_values, rrset_ttl)))
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Keep the values sent to or read from Gandi
+    pub(crate) async fn set(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+        rrset_values: &[String],
+        rrset_ttl: Option<u32>,
+    ) {
+        let record = CachedRecord {
+            fqdn: fqdn.to_owned(),
+            rrset_name: rrset_name.to_owned(),
+            rrset_type: rrset_type.to_owned(),
+            rrset_values: rrset_values.to_owned(),
+            rrset_ttl,
+            checked: now(),
+        };
+
+        self.records
+            .lock()
+            .expect("cache lock")
+            .insert(record.key(), record);
+
+        self.save().await;
+    }
+
+    /// Write the records to the state file, without holding the lock of the records
+    ///
+    /// Writes are done one at a time, each one with the records at the time it starts.
+    async fn save(&self) {
+        let Some(path) = &self.state_file else {
+            return;
+        };
+
+        let _writing = self.writing.lock().await;
+
+        let mut state: Vec<CachedRecord> = self
+            .records
+            .lock()
+            .expect("cache lock")
+            .values()
+            .cloned()
+            .collect();
+        state.sort_by_key(|record| record.key());
+
+        let written = match serde_yaml::to_string(&state) {
+            Ok(state) => tokio::fs::write(path, state)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        if let Err(e) = written {
+            tracing::error!("cache: {} ({})", e, path.display());
+        }
+    }
+
+    /// Returns the cached record if it was checked during the refresh interval
+    fn fresh(&self, key: &Key) -> Option<CachedRecord> {
+        let records = self.records.lock().expect("cache lock");
+
+        records
+            .get(key)
+            .filter(|record| now().saturating_sub(record.checked) < self.refresh_interval.as_secs())
+            .cloned()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn key() -> Key {
+        ("example.org".to_owned(), "home".to_owned(), "A".to_owned())
+    }
+
+    #[tokio::test]
+    async fn fresh_records() {
+        let cache = RecordCache::build(&CacheConfig::default()).unwrap();
+        cache
+            .set(
+                "example.org",
+                "home",
+                "A",
+                &["192.0.2.1".to_owned()],
+                Some(300),
+            )
+            .await;
+
+        let cached = cache.fresh(&key()).unwrap();
+        assert!(cached.same(&["192.0.2.1".to_owned()], 300));
+        assert!(!cached.same(&["192.0.2.1".to_owned()], 600));
+        assert!(!cached.same(&["192.0.2.2".to_owned()], 300));
+
+        let stale = RecordCache {
+            refresh_interval: Duration::ZERO,
+            ..cache
+        };
+        assert_eq!(stale.fresh(&key()), None);
+    }
+
+    #[tokio::test]
+    async fn state_file() {
+        let path = env::temp_dir().join(format!("custom-ddns-cache-{}.yaml", std::process::id()));
+        let config = CacheConfig {
+            state_file: Some(path.to_owned()),
+            ..Default::default()
+        };
+
+        let cache = RecordCache::build(&config).unwrap();
+        cache
+            .set(
+                "example.org",
+                "home",
+                "A",
+                &["192.0.2.1".to_owned()],
+                Some(300),
+            )
+            .await;
+
+        let restarted = RecordCache::build(&config).unwrap();
+        assert_eq!(
+            restarted.fresh(&key()).map(|cached| cached.rrset_values),
+            Some(vec!["192.0.2.1".to_owned()])
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+}
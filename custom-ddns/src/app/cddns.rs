@@ -1,13 +1,18 @@
-use std::sync::Arc;
+use std::net::{IpAddr, SocketAddr};
 
 use axum::{
-    extract::{Path, Query, State},
+    extract::{ConnectInfo, Path, Query, State},
     http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
 };
 use gandi_v5_livedns_api::{records::UpsertRecord, Api, Endpoint};
 use serde::Deserialize;
 
-use crate::config::AppConfig;
+use super::client_ip::client_ip;
+use super::limits::Authentication;
+use super::AppState;
+use crate::config::{AppConfig, Authorized, Denied};
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct Record {
@@ -17,6 +22,14 @@ pub(crate) struct Record {
     rrset_value: String,
 }
 
+/// A record updated with the address of the client
+#[derive(Debug, Deserialize)]
+pub(crate) struct ClientRecord {
+    fqdn: String,
+    rrset_name: String,
+    rrset_type: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct Extra {
     rrset_ttl: Option<u32>,
@@ -24,42 +37,122 @@ pub(crate) struct Extra {
 
 pub(crate) async fn gandi(
     headers: HeaderMap,
-    Path(Record {
+    Path(record): Path<Record>,
+    Query(Extra { rrset_ttl }): Query<Extra>,
+    State(state): State<AppState>,
+) -> Response {
+    update(&headers, record, rrset_ttl, &state).await
+}
+
+/// Update a record with the address of the client (the peer or the client of trusted proxies)
+pub(crate) async fn gandi_client_ip(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(ClientRecord {
         fqdn,
         rrset_name,
         rrset_type,
-        rrset_value,
-    }): Path<Record>,
+    }): Path<ClientRecord>,
     Query(Extra { rrset_ttl }): Query<Extra>,
-    State(config): State<Arc<AppConfig>>,
-) -> (StatusCode, String) {
+    State(state): State<AppState>,
+) -> Response {
+    let ip = client_ip(peer.ip(), &headers, &state.config.load().trusted_proxies);
+
+    if !matches!(rrset_type.as_str(), "A" | "AAAA") {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Client ip {} can't be used for type {}", ip, rrset_type),
+        )
+            .into_response();
+    }
+
+    let record = Record {
+        fqdn,
+        rrset_name,
+        rrset_type,
+        rrset_value: ip.to_string(),
+    };
+
+    update(&headers, record, rrset_ttl, &state).await
+}
+
+/// Authenticate the request, then update the record
+///
+/// The authentication is set in the extensions of the response for the limits.
+async fn update(
+    headers: &HeaderMap,
+    record: Record,
+    rrset_ttl: Option<u32>,
+    state: &AppState,
+) -> Response {
+    let config = state.config.load_full();
     let auth_header = match headers.get("authorization") {
         Some(auth_header) => match auth_header.to_str() {
             Ok(auth_header) => auth_header,
-            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()),
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
         },
         None => {
             return (
                 StatusCode::FORBIDDEN,
                 "No authorization header provided".to_owned(),
             )
+                .into_response()
         }
     };
 
     let rrset_ttl = rrset_ttl.unwrap_or(config.default_rrset_ttl);
 
-    let personal_access_token = match config.get_pat_if_authorized(
-        &fqdn,
-        &rrset_name,
-        &rrset_type,
+    let authorized = match config.get_pat_if_authorized(
+        &record.fqdn,
+        &record.rrset_name,
+        &record.rrset_type,
         rrset_ttl,
-        &auth_header,
+        auth_header,
     ) {
-        Ok(personal_access_token) => personal_access_token,
-        Err(e) => return (StatusCode::FORBIDDEN, e),
+        Ok(authorized) => authorized,
+        Err(e @ Denied::NotWhitelisted(_)) => {
+            return (StatusCode::FORBIDDEN, e.to_string()).into_response()
+        }
+        Err(e @ Denied::Forbidden(_)) => {
+            return (
+                StatusCode::FORBIDDEN,
+                Extension(Authentication::Refused),
+                e.to_string(),
+            )
+                .into_response()
+        }
     };
 
-    let api = match Api::build(Endpoint::Prod, &personal_access_token) {
+    (
+        Extension(Authentication::Verified),
+        upsert(state, &config, &authorized, &record, rrset_ttl).await,
+    )
+        .into_response()
+}
+
+async fn upsert(
+    state: &AppState,
+    config: &AppConfig,
+    authorized: &Authorized,
+    record: &Record,
+    rrset_ttl: u32,
+) -> (StatusCode, String) {
+    let Record {
+        fqdn,
+        rrset_name,
+        rrset_type,
+        rrset_value,
+    } = record;
+
+    // the values of other types (eg: TXT, CNAME) aren't addresses
+    if matches!(rrset_type.as_str(), "A" | "AAAA") {
+        if let Err(e) = check_ip(config, fqdn, rrset_name, rrset_type, rrset_value) {
+            tracing::warn!("{} refused: {}", authorized.client, e);
+            return (StatusCode::BAD_REQUEST, e);
+        }
+    }
+
+    let api = match Api::build(Endpoint::Prod, authorized.personal_access_token.expose()) {
         Ok(api) => api,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.as_ref().to_string()),
     };
@@ -69,17 +162,111 @@ pub(crate) async fn gandi(
         rrset_ttl: Some(rrset_ttl),
     };
 
-    match api
-        .upsert_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type, &record)
+    match state
+        .records
+        .unchanged(
+            &api,
+            fqdn,
+            rrset_name,
+            rrset_type,
+            &record.rrset_values,
+            rrset_ttl,
+        )
+        .await
+    {
+        Ok(true) => {
+            return (
+                StatusCode::OK,
+                format!(
+                    "Record {}.{} type {} already has ip {} (nochg)",
+                    rrset_name, fqdn, rrset_type, rrset_value
+                ),
+            )
+        }
+        Ok(false) => {}
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+
+    if let Err(e) = state.limits.check_update(fqdn, rrset_name, rrset_type) {
+        tracing::warn!("limits: {}", e);
+        return (StatusCode::TOO_MANY_REQUESTS, e);
+    }
+
+    if let Err(e) = api
+        .upsert_record_by_name_and_type(fqdn, rrset_name, rrset_type, &record)
         .await
+        .map_err(|e| e.to_string())
     {
-        Ok(_) => (
-            StatusCode::OK,
-            format!(
-                "Record {}.{} type {} updated with ip {} !",
-                rrset_name, fqdn, rrset_type, rrset_value
-            ),
+        return (StatusCode::INTERNAL_SERVER_ERROR, e);
+    }
+
+    state.limits.updated(fqdn, rrset_name, rrset_type);
+    state
+        .records
+        .set(
+            fqdn,
+            rrset_name,
+            rrset_type,
+            &record.rrset_values,
+            record.rrset_ttl,
+        )
+        .await;
+
+    tracing::info!(
+        "{} updated {}.{} type {} with {}",
+        authorized.client,
+        rrset_name,
+        fqdn,
+        rrset_type,
+        rrset_value
+    );
+
+    (
+        StatusCode::OK,
+        format!(
+            "Record {}.{} type {} updated with ip {} !",
+            rrset_name, fqdn, rrset_type, rrset_value
         ),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.as_ref().to_string()),
+    )
+}
+
+/// Returns an error if the value isn't an address of the type allowed by the whitelisted record
+fn check_ip(
+    config: &AppConfig,
+    fqdn: &str,
+    rrset_name: &str,
+    rrset_type: &str,
+    rrset_value: &str,
+) -> Result<(), String> {
+    let ip = rrset_value
+        .parse::<IpAddr>()
+        .map_err(|_| format!("Invalid ip {}", rrset_value))?;
+
+    if !family_of(rrset_type, &ip) {
+        return Err(format!("Ip {} can't be used for type {}", ip, rrset_type));
+    }
+
+    config.check_address(fqdn, rrset_name, rrset_type, &ip)
+}
+
+/// Returns true if the address can be the value of a record of the type (A for IPv4, AAAA for IPv6)
+fn family_of(rrset_type: &str, ip: &IpAddr) -> bool {
+    matches!(
+        (rrset_type, ip),
+        ("A", IpAddr::V4(_)) | ("AAAA", IpAddr::V6(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_family() {
+        assert!(family_of("A", &"192.0.2.1".parse().unwrap()));
+        assert!(family_of("AAAA", &"2001:db8::1".parse().unwrap()));
+        assert!(!family_of("A", &"2001:db8::1".parse().unwrap()));
+        assert!(!family_of("AAAA", &"192.0.2.1".parse().unwrap()));
+        assert!(!family_of("TXT", &"192.0.2.1".parse().unwrap()));
     }
 }
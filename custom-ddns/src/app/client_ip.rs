@@ -0,0 +1,137 @@
+use std::net::IpAddr;
+
+use axum::http::{header, HeaderMap};
+use ipnet::IpNet;
+
+/// Returns the address of the client of a request
+///
+/// Forwarding headers (`Forwarded`, then `X-Forwarded-For`) are only read when the peer is a trusted proxy:
+/// the client is the last address which isn't a trusted proxy.
+pub(crate) fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let peer = peer.to_canonical();
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+
+    if !trusted(&peer) {
+        return peer;
+    }
+
+    let forwarded = match forwarded(headers) {
+        forwarded if !forwarded.is_empty() => forwarded,
+        _ => x_forwarded_for(headers),
+    };
+
+    forwarded
+        .iter()
+        .rev()
+        .find(|ip| !trusted(ip))
+        .or(forwarded.first())
+        .copied()
+        .unwrap_or(peer)
+}
+
+/// Addresses of the `for` parameters of the `Forwarded` headers (RFC 7239)
+fn forwarded(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+
+                key.eq_ignore_ascii_case("for").then(|| node(value))?
+            })
+        })
+        .collect()
+}
+
+/// Addresses of the `X-Forwarded-For` headers
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(node)
+        .collect()
+}
+
+/// Address of a node: `192.0.2.1`, `192.0.2.1:80`, `"[2001:db8::1]:80"` or `2001:db8::1`
+fn node(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+
+    let host = match value.strip_prefix('[') {
+        Some(value) => value.split_once(']')?.0,
+        None => value.rsplit_once(':')?.0,
+    };
+
+    host.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()]
+    }
+
+    #[test]
+    fn untrusted_peer() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("192.0.2.1"));
+
+        assert_eq!(
+            client_ip(ip("198.51.100.7"), &headers, &trusted()),
+            ip("198.51.100.7")
+        );
+        assert_eq!(
+            client_ip(ip("::ffff:198.51.100.7"), &headers, &[]),
+            ip("198.51.100.7")
+        );
+    }
+
+    #[test]
+    fn trusted_proxies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("203.0.113.9, 192.0.2.1, 10.0.0.2"),
+        );
+
+        // the client can't spoof addresses before the first trusted proxy
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted()),
+            ip("192.0.2.1")
+        );
+
+        headers.insert(
+            header::FORWARDED,
+            HeaderValue::from_static("for=\"[2001:db8::1]:4711\";proto=https, for=10.0.0.2"),
+        );
+
+        assert_eq!(
+            client_ip(ip("::1"), &headers, &trusted()),
+            ip("2001:db8::1")
+        );
+    }
+
+    #[test]
+    fn nodes() {
+        assert_eq!(node("192.0.2.1:80"), Some(ip("192.0.2.1")));
+        assert_eq!(node("2001:db8::1"), Some(ip("2001:db8::1")));
+        assert_eq!(node("\"[2001:db8::1]\""), Some(ip("2001:db8::1")));
+        assert_eq!(node("unknown"), None);
+        assert_eq!(node("_hidden"), None);
+    }
+}
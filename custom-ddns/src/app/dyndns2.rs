@@ -0,0 +1,395 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    extract::{ConnectInfo, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use gandi_v5_livedns_api::{records::UpsertRecord, Api, Endpoint};
+
+use super::{client_ip::client_ip, limits::Authentication, AppState};
+use crate::config::Denied;
+
+/// Parameters of an update (`hostname` and `myip` are comma separated or repeated)
+#[derive(Debug, Default, PartialEq)]
+struct Update {
+    hostnames: Vec<String>,
+    myips: Vec<String>,
+}
+
+impl Update {
+    fn parse(query: &str) -> Self {
+        let mut update = Update::default();
+
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            let values = value
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_owned);
+
+            match key.as_ref() {
+                "hostname" => update.hostnames.extend(values),
+                "myip" => update.myips.extend(values),
+                _ => {}
+            }
+        }
+
+        update
+    }
+}
+
+/// Return codes of the DynDNS2 protocol
+#[derive(Debug, PartialEq)]
+enum Code {
+    /// The record is updated with the addresses
+    Good(String),
+    /// The record already has the addresses
+    NoChg(String),
+    BadAuth,
+    /// The hostname isn't fully qualified, or an address is invalid
+    NotFqdn,
+    /// The hostname isn't whitelisted, or can't have the addresses
+    NoHost,
+    /// Too many requests or updates
+    Abuse,
+    /// Server error
+    Fatal,
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Code::Good(ips) => write!(f, "good {}", ips),
+            Code::NoChg(ips) => write!(f, "nochg {}", ips),
+            Code::BadAuth => write!(f, "badauth"),
+            Code::NotFqdn => write!(f, "notfqdn"),
+            Code::NoHost => write!(f, "nohost"),
+            Code::Abuse => write!(f, "abuse"),
+            Code::Fatal => write!(f, "911"),
+        }
+    }
+}
+
+/// DynDNS2 update (`/nic/update?hostname=home.example.org&myip=1.2.3.4`) with a Basic authorization
+///
+/// Without `myip`, the address of the client is used. One code is returned per hostname.
+pub(crate) async fn nic_update(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+    State(state): State<AppState>,
+) -> Response {
+    let Some(authorization) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+    else {
+        return response(vec![Code::BadAuth]);
+    };
+
+    let update = Update::parse(query.as_deref().unwrap_or_default());
+
+    if update.hostnames.is_empty() {
+        return response(vec![Code::NotFqdn]);
+    }
+
+    let ips = match update.myips.is_empty() {
+        true => vec![client_ip(
+            peer.ip(),
+            &headers,
+            &state.config.load().trusted_proxies,
+        )],
+        false => match addresses(&update.myips) {
+            Some(ips) => ips,
+            None => {
+                tracing::warn!("dyndns2: invalid myip {:?}", update.myips);
+                return response(vec![Code::NotFqdn]);
+            }
+        },
+    };
+
+    let mut codes = vec![];
+    let mut authentication = None;
+
+    for hostname in &update.hostnames {
+        let (code, authenticated) = update_hostname(&state, hostname, &ips, authorization).await;
+
+        // a credential verified for a hostname isn't counted as a failure for another one
+        if authentication != Some(Authentication::Verified) {
+            authentication = authenticated.or(authentication);
+        }
+
+        codes.push(code);
+    }
+
+    let mut response = response(codes);
+
+    if let Some(authentication) = authentication {
+        response.extensions_mut().insert(authentication);
+    }
+
+    response
+}
+
+/// Parsed addresses, None if an address is invalid or none is provided
+fn addresses(myips: &[String]) -> Option<Vec<IpAddr>> {
+    let ips: Vec<IpAddr> = myips
+        .iter()
+        .map(|ip| ip.parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    (!ips.is_empty()).then_some(ips)
+}
+
+/// Values of the A and AAAA records for the addresses
+fn records(ips: &[IpAddr]) -> BTreeMap<&'static str, Vec<String>> {
+    let mut records: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for ip in ips {
+        let rrset_type = match ip {
+            IpAddr::V4(_) => "A",
+            IpAddr::V6(_) => "AAAA",
+        };
+
+        let values = records.entry(rrset_type).or_default();
+
+        if !values.contains(&ip.to_string()) {
+            values.push(ip.to_string());
+        }
+    }
+
+    records
+}
+
+/// Update the A and AAAA records of a hostname, once every type is authorized and allowed
+async fn update_hostname(
+    state: &AppState,
+    hostname: &str,
+    ips: &[IpAddr],
+    authorization: &str,
+) -> (Code, Option<Authentication>) {
+    let config = state.config.load_full();
+
+    let Some((fqdn, rrset_name)) = config.split_hostname(hostname) else {
+        let code = match hostname.trim_end_matches('.').contains('.') {
+            true => Code::NoHost,
+            false => Code::NotFqdn,
+        };
+
+        return (code, None);
+    };
+
+    let rrset_ttl = config.default_rrset_ttl;
+    let mut authentication = None;
+    let mut authorized = vec![];
+
+    // every type is authorized and allowed before any record is updated
+    for (rrset_type, rrset_values) in records(ips) {
+        match config.get_pat_if_authorized(&fqdn, &rrset_name, rrset_type, rrset_ttl, authorization)
+        {
+            Ok(granted) => {
+                authentication = Some(Authentication::Verified);
+                authorized.push((rrset_type, rrset_values, granted));
+            }
+            Err(e) => {
+                tracing::warn!("dyndns2: {}", e);
+
+                return match e {
+                    Denied::NotWhitelisted(_) => (Code::NoHost, authentication),
+                    Denied::Forbidden(_) => (Code::BadAuth, Some(Authentication::Refused)),
+                };
+            }
+        }
+    }
+
+    for (rrset_type, rrset_values, granted) in &authorized {
+        for ip in ips
+            .iter()
+            .filter(|ip| rrset_values.contains(&ip.to_string()))
+        {
+            if let Err(e) = config.check_address(&fqdn, &rrset_name, rrset_type, ip) {
+                tracing::warn!("dyndns2: {} refused: {}", granted.client, e);
+                return (Code::NoHost, authentication);
+            }
+        }
+    }
+
+    let mut updates = vec![];
+
+    for (rrset_type, rrset_values, granted) in authorized {
+        let api = match Api::build(Endpoint::Prod, granted.personal_access_token.expose()) {
+            Ok(api) => api,
+            Err(e) => {
+                tracing::error!("dyndns2: {}", e);
+                return (Code::Fatal, authentication);
+            }
+        };
+
+        match state
+            .records
+            .unchanged(
+                &api,
+                &fqdn,
+                &rrset_name,
+                rrset_type,
+                &rrset_values,
+                rrset_ttl,
+            )
+            .await
+        {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("dyndns2: {}", e);
+                return (Code::Fatal, authentication);
+            }
+        }
+
+        if let Err(e) = state.limits.check_update(&fqdn, &rrset_name, rrset_type) {
+            tracing::warn!("limits: {}", e);
+            return (Code::Abuse, authentication);
+        }
+
+        updates.push((api, rrset_type, rrset_values, granted));
+    }
+
+    let changed = !updates.is_empty();
+
+    for (api, rrset_type, rrset_values, granted) in updates {
+        let record = UpsertRecord {
+            rrset_values,
+            rrset_ttl: Some(rrset_ttl),
+        };
+
+        if let Err(e) = api
+            .upsert_record_by_name_and_type(&fqdn, &rrset_name, rrset_type, &record)
+            .await
+        {
+            tracing::error!("dyndns2: {}", e);
+            return (Code::Fatal, authentication);
+        }
+
+        state.limits.updated(&fqdn, &rrset_name, rrset_type);
+        state
+            .records
+            .set(
+                &fqdn,
+                &rrset_name,
+                rrset_type,
+                &record.rrset_values,
+                record.rrset_ttl,
+            )
+            .await;
+
+        tracing::info!(
+            "{} updated {}.{} type {} with {}",
+            granted.client,
+            rrset_name,
+            fqdn,
+            rrset_type,
+            record.rrset_values.join(",")
+        );
+    }
+
+    let ips = ips
+        .iter()
+        .map(|ip| ip.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let code = match changed {
+        true => Code::Good(ips),
+        false => Code::NoChg(ips),
+    };
+
+    (code, authentication)
+}
+
+/// Codes one per line, with a Basic challenge when the authorization is refused
+/// (429 Too Many Requests when every update is limited)
+fn response(codes: Vec<Code>) -> Response {
+    let body = codes
+        .iter()
+        .map(|code| code.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if codes.iter().all(|code| *code == Code::BadAuth) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"custom-ddns\"")],
+            body,
+        )
+            .into_response();
+    }
+
+    if codes.iter().all(|code| *code == Code::Abuse) {
+        return (StatusCode::TOO_MANY_REQUESTS, body).into_response();
+    }
+
+    (StatusCode::OK, body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_update() {
+        let update = Update::parse(
+            "hostname=home.example.org,nas.example.org&myip=192.0.2.1&myip=2001%3Adb8%3A%3A1&wildcard=NOCHG",
+        );
+
+        assert_eq!(
+            update,
+            Update {
+                hostnames: vec!["home.example.org".to_owned(), "nas.example.org".to_owned()],
+                myips: vec!["192.0.2.1".to_owned(), "2001:db8::1".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn records_by_family() {
+        let ips = addresses(&[
+            "192.0.2.1".to_owned(),
+            "2001:db8::1".to_owned(),
+            "192.0.2.2".to_owned(),
+        ])
+        .unwrap();
+
+        let records = records(&ips);
+
+        assert_eq!(records["A"], vec!["192.0.2.1", "192.0.2.2"]);
+        assert_eq!(records["AAAA"], vec!["2001:db8::1"]);
+
+        assert_eq!(addresses(&["192.0.2.300".to_owned()]), None);
+        assert_eq!(addresses(&[]), None);
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(
+            Code::Good("192.0.2.1".to_owned()).to_string(),
+            "good 192.0.2.1"
+        );
+        assert_eq!(Code::Fatal.to_string(), "911");
+
+        let refused = response(vec![Code::BadAuth]);
+        assert_eq!(refused.status(), StatusCode::UNAUTHORIZED);
+        assert!(refused.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let partial = response(vec![Code::BadAuth, Code::NoChg("192.0.2.1".to_owned())]);
+        assert_eq!(partial.status(), StatusCode::OK);
+
+        assert_eq!(
+            response(vec![Code::Abuse]).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::{client_ip::client_ip, AppState};
+use crate::{config::LimitsConfig, credentials};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// A client limited independently: its address or its credential
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Client {
+    Ip(IpAddr),
+    Credential(String),
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Client::Ip(ip) => write!(f, "ip {}", ip),
+            Client::Credential(username) => write!(f, "credential {}", username),
+        }
+    }
+}
+
+/// Authentication of a request, set by the handlers in the extensions of the response
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Authentication {
+    /// The credential is verified
+    Verified,
+    /// The credential is refused (bad password or not granted)
+    Refused,
+}
+
+/// Requests of a client during the current minute
+struct Window {
+    start: Instant,
+    requests: u32,
+}
+
+/// Failed authentications of a client address
+struct Failures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Counters {
+    windows: HashMap<Client, Window>,
+    failures: HashMap<IpAddr, Failures>,
+    /// Last update of a record (fqdn, rrset_name and rrset_type)
+    updates: HashMap<(String, String, String), Instant>,
+    pruned: Option<Instant>,
+}
+
+/// Rate limits of the clients and lockout after failed authentications
+pub(crate) struct Limiter {
+    requests_per_ip: u32,
+    requests_per_credential: u32,
+    max_failures: u32,
+    lockout: Duration,
+    min_update_interval: Duration,
+    counters: Mutex<Counters>,
+}
+
+impl Limiter {
+    pub(crate) fn build(config: &LimitsConfig) -> Self {
+        Limiter {
+            requests_per_ip: config.requests_per_minute_per_ip,
+            requests_per_credential: config.requests_per_minute_per_credential,
+            max_failures: config.max_failed_authentications,
+            lockout: Duration::from_secs(config.lockout),
+            min_update_interval: Duration::from_secs(config.min_update_interval),
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+
+    /// Count a request of the address, returns why it's refused
+    ///
+    /// The credential isn't counted before it's verified, so requests with a wrong password
+    /// can't exhaust the limit of the router using it.
+    fn request(&self, ip: IpAddr, username: Option<&str>, now: Instant) -> Result<(), String> {
+        let mut counters = self.counters.lock().expect("limits lock");
+        self.prune(&mut counters, now);
+
+        if let Some(until) = counters
+            .failures
+            .get(&ip)
+            .and_then(|failures| failures.locked_until)
+            .filter(|until| *until > now)
+        {
+            return Err(format!(
+                "{} locked out for {}s after failed authentications",
+                Client::Ip(ip),
+                until.duration_since(now).as_secs()
+            ));
+        }
+
+        if let Some(username) = username {
+            let client = Client::Credential(username.to_owned());
+
+            if self.requests_per_credential > 0
+                && counters.windows.get(&client).is_some_and(|window| {
+                    now.duration_since(window.start) < WINDOW
+                        && window.requests >= self.requests_per_credential
+                })
+            {
+                return Err(format!(
+                    "{} exceeds {} requests per minute",
+                    client, self.requests_per_credential
+                ));
+            }
+        }
+
+        Self::count(&mut counters, Client::Ip(ip), self.requests_per_ip, now)
+    }
+
+    /// Count a request in the window of a client, returns an error over the limit (0: unlimited)
+    fn count(
+        counters: &mut Counters,
+        client: Client,
+        limit: u32,
+        now: Instant,
+    ) -> Result<(), String> {
+        if limit == 0 {
+            return Ok(());
+        }
+
+        let window = counters.windows.entry(client.clone()).or_insert(Window {
+            start: now,
+            requests: 0,
+        });
+
+        if now.duration_since(window.start) >= WINDOW {
+            window.start = now;
+            window.requests = 0;
+        }
+
+        window.requests += 1;
+
+        match window.requests > limit {
+            true => Err(format!("{} exceeds {} requests per minute", client, limit)),
+            false => Ok(()),
+        }
+    }
+
+    /// Count a failed authentication of the address, returns true if it's locked out
+    fn failed(&self, ip: IpAddr, now: Instant) -> bool {
+        if self.max_failures == 0 {
+            return false;
+        }
+
+        let mut counters = self.counters.lock().expect("limits lock");
+        let failures = counters.failures.entry(ip).or_insert(Failures {
+            count: 0,
+            last: now,
+            locked_until: None,
+        });
+
+        // failures are forgotten after a lockout period without failure
+        if now.duration_since(failures.last) >= self.lockout {
+            failures.count = 0;
+        }
+
+        failures.count += 1;
+        failures.last = now;
+
+        if failures.count >= self.max_failures {
+            failures.count = 0;
+            failures.locked_until = Some(now + self.lockout);
+            return true;
+        }
+
+        false
+    }
+
+    /// Forget the failed authentications of the address and count the request of the verified credential
+    fn verified(&self, ip: IpAddr, username: Option<&str>, now: Instant) {
+        let mut counters = self.counters.lock().expect("limits lock");
+
+        counters.failures.remove(&ip);
+
+        if let Some(username) = username {
+            // the request is already accepted, the limit applies to the next ones
+            _ = Self::count(
+                &mut counters,
+                Client::Credential(username.to_owned()),
+                self.requests_per_credential,
+                now,
+            );
+        }
+    }
+
+    /// Returns an error if the record was updated less than the minimum interval ago
+    pub(crate) fn check_update(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+    ) -> Result<(), String> {
+        self.check_update_at(fqdn, rrset_name, rrset_type, Instant::now())
+    }
+
+    fn check_update_at(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+        now: Instant,
+    ) -> Result<(), String> {
+        let counters = self.counters.lock().expect("limits lock");
+
+        match counters
+            .updates
+            .get(&update_key(fqdn, rrset_name, rrset_type))
+        {
+            Some(last) if now.duration_since(*last) < self.min_update_interval => Err(format!(
+                "record {}.{} type {} updated {}s ago (minimum interval {}s)",
+                rrset_name,
+                fqdn,
+                rrset_type,
+                now.duration_since(*last).as_secs(),
+                self.min_update_interval.as_secs()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keep the time of a successful update of the record
+    pub(crate) fn updated(&self, fqdn: &str, rrset_name: &str, rrset_type: &str) {
+        self.updated_at(fqdn, rrset_name, rrset_type, Instant::now())
+    }
+
+    fn updated_at(&self, fqdn: &str, rrset_name: &str, rrset_type: &str, now: Instant) {
+        let mut counters = self.counters.lock().expect("limits lock");
+
+        counters
+            .updates
+            .insert(update_key(fqdn, rrset_name, rrset_type), now);
+    }
+
+    /// Forget expired windows, failures and updates (once per minute)
+    fn prune(&self, counters: &mut Counters, now: Instant) {
+        if counters
+            .pruned
+            .is_some_and(|pruned| now.duration_since(pruned) < WINDOW)
+        {
+            return;
+        }
+
+        counters
+            .windows
+            .retain(|_, window| now.duration_since(window.start) < WINDOW);
+        counters.failures.retain(|_, failures| {
+            now.duration_since(failures.last) < self.lockout
+                || failures.locked_until.is_some_and(|until| until > now)
+        });
+        counters
+            .updates
+            .retain(|_, last| now.duration_since(*last) < self.min_update_interval);
+        counters.pruned = Some(now);
+    }
+}
+
+fn update_key(fqdn: &str, rrset_name: &str, rrset_type: &str) -> (String, String, String) {
+    (
+        fqdn.to_owned(),
+        rrset_name.to_owned(),
+        rrset_type.to_owned(),
+    )
+}
+
+/// Middleware refusing requests of clients over the rate limits or locked out (429 Too Many Requests)
+///
+/// Credentials refused by the handlers are counted as failed authentications of the client address.
+pub(crate) async fn limit(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let ip = client_ip(peer.ip(), headers, &state.config.load().trusted_proxies);
+    let username = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(credentials::basic)
+        .map(|(username, _)| username);
+
+    if let Err(e) = state
+        .limits
+        .request(ip, username.as_deref(), Instant::now())
+    {
+        tracing::warn!("limits: {}", e);
+        return too_many_requests(e);
+    }
+
+    let response = next.run(request).await;
+
+    match response.extensions().get::<Authentication>() {
+        Some(Authentication::Verified) => {
+            state
+                .limits
+                .verified(ip, username.as_deref(), Instant::now())
+        }
+        Some(Authentication::Refused) if state.limits.failed(ip, Instant::now()) => {
+            tracing::warn!(
+                "limits: {} locked out for {}s after failed authentications",
+                Client::Ip(ip),
+                state.limits.lockout.as_secs()
+            );
+        }
+        _ => {}
+    }
+
+    response
+}
+
+pub(crate) fn too_many_requests(reason: String) -> Response {
+    (StatusCode::TOO_MANY_REQUESTS, reason).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> Limiter {
+        Limiter::build(&LimitsConfig {
+            requests_per_minute_per_ip: 3,
+            requests_per_minute_per_credential: 2,
+            max_failed_authentications: 2,
+            lockout: 900,
+            min_update_interval: 60,
+        })
+    }
+
+    fn ip() -> IpAddr {
+        "192.0.2.1".parse().unwrap()
+    }
+
+    #[test]
+    fn requests_per_minute() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        assert!(limiter.request(ip(), Some("router"), now).is_ok());
+        assert!(limiter.request(ip(), Some("router"), now).is_ok());
+        assert!(limiter.request(ip(), Some("router"), now).is_ok());
+        assert!(limiter.request(ip(), Some("router"), now).is_err());
+
+        // the credential is only counted once verified
+        let other = "192.0.2.2".parse().unwrap();
+        assert!(limiter.request(other, Some("router"), now).is_ok());
+        limiter.verified(other, Some("router"), now);
+        limiter.verified(other, Some("router"), now);
+        assert!(limiter.request(other, Some("router"), now).is_err());
+        assert!(limiter.request(other, None, now).is_ok());
+
+        assert!(limiter.request(ip(), Some("router"), now + WINDOW).is_ok());
+    }
+
+    #[test]
+    fn lockout() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        assert!(!limiter.failed(ip(), now));
+        assert!(limiter.failed(ip(), now));
+
+        assert!(limiter.request(ip(), None, now).is_err());
+        // the username isn't locked out from other addresses
+        assert!(limiter
+            .request("192.0.2.2".parse().unwrap(), Some("router"), now)
+            .is_ok());
+        assert!(limiter.request(ip(), None, now + limiter.lockout).is_ok());
+
+        // a verified credential forgets the failures
+        limiter.failed(ip(), now + limiter.lockout);
+        limiter.verified(ip(), Some("router"), now + limiter.lockout);
+        assert!(!limiter.failed(ip(), now + limiter.lockout));
+    }
+
+    #[test]
+    fn min_update_interval() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        // a failed update doesn't delay the next one
+        assert!(limiter
+            .check_update_at("example.org", "home", "A", now)
+            .is_ok());
+        assert!(limiter
+            .check_update_at("example.org", "home", "A", now)
+            .is_ok());
+
+        limiter.updated_at("example.org", "home", "A", now);
+        assert!(limiter
+            .check_update_at("example.org", "home", "A", now)
+            .is_err());
+        assert!(limiter
+            .check_update_at("example.org", "home", "AAAA", now)
+            .is_ok());
+        assert!(limiter
+            .check_update_at("example.org", "home", "A", now + Duration::from_secs(60))
+            .is_ok());
+    }
+}
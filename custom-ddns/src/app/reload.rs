@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use config::ConfigError;
+use tokio::signal;
+
+use super::SharedConfig;
+use crate::config::AppConfig;
+
+/// Delay between two checks of the configuration files
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Reload the configuration on SIGHUP
+#[cfg(unix)]
+pub(crate) async fn on_signal(config: SharedConfig) {
+    let mut hangup = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::error!("config: SIGHUP handler not installed: {}", e);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        tracing::info!("config: SIGHUP received");
+        reload(&config).await;
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn on_signal(_config: SharedConfig) {}
+
+/// Reload the configuration when its files are modified
+pub(crate) async fn on_change(config: SharedConfig) {
+    let mut modified = last_modified();
+
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let current = last_modified();
+
+        if current != modified {
+            modified = current;
+            tracing::info!("config: files modified");
+            reload(&config).await;
+        }
+    }
+}
+
+/// Swap the configuration with a new one, if it's valid
+///
+/// Requests being served keep the previous configuration. Listeners, tls, cache and limits are only read at startup.
+async fn reload(config: &SharedConfig) {
+    let shared = config.clone();
+
+    match tokio::task::spawn_blocking(move || swap(&shared, &AppConfig::files())).await {
+        Ok(Ok(())) => tracing::info!("config: reloaded"),
+        Ok(Err(e)) => tracing::error!("config: reload refused, previous configuration kept: {}", e),
+        Err(e) => tracing::error!("config: reload failed, previous configuration kept: {}", e),
+    }
+}
+
+/// Build the configuration of the files and store it, only if it's valid
+fn swap(config: &SharedConfig, files: &[PathBuf]) -> Result<(), ConfigError> {
+    let new = AppConfig::build_from(files)?;
+    config.store(Arc::new(new));
+
+    Ok(())
+}
+
+/// Modification times of the configuration files (None if missing)
+fn last_modified() -> Vec<Option<SystemTime>> {
+    AppConfig::files()
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arc_swap::ArcSwap;
+    use std::env;
+
+    const CONFIG: &str = r#"
+listen: '127.0.0.1:3000'
+default_rrset_ttl: 300
+users:
+- username: router
+  # 1234
+  password_hash: '$argon2id$v=19$m=19456,t=2,p=1$n1BPN42gNM6jzA4VjgLkiQ$rSmRhZP0Kd3rkGdl6ILzyo1yV8A7Z72yMk0BS59d8dQ'
+  grants:
+  - fqdn: example.org
+    rrset_name: home
+    rrset_type: A
+whitelist:
+  example.org:
+    personal_access_token: TOKEN
+    records:
+    - rrset_name: home
+      rrset_type: A
+      rrset_ttl_max: 10800
+"#;
+
+    fn file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!(
+            "custom-ddns-reload-{}-{}.yaml",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn invalid_configs_are_refused() {
+        let valid = file("valid", CONFIG);
+        let config: SharedConfig = Arc::new(ArcSwap::from_pointee(
+            AppConfig::build_from(&[valid.to_owned()]).unwrap(),
+        ));
+        let current = config.load_full();
+
+        let invalid_yaml = file("yaml", "listen: [");
+        assert!(swap(&config, &[invalid_yaml.to_owned()]).is_err());
+        assert!(Arc::ptr_eq(&config.load_full(), &current));
+
+        // a password instead of its hash
+        let invalid_hash = file(
+            "hash",
+            &CONFIG.replace(
+                "$argon2id$v=19$m=19456,t=2,p=1$n1BPN42gNM6jzA4VjgLkiQ$rSmRhZP0Kd3rkGdl6ILzyo1yV8A7Z72yMk0BS59d8dQ",
+                "1234",
+            ),
+        );
+        assert!(swap(&config, &[invalid_hash.to_owned()]).is_err());
+        assert!(Arc::ptr_eq(&config.load_full(), &current));
+
+        let modified = file("modified", &CONFIG.replace("3000", "3001"));
+        assert!(swap(&config, &[modified.to_owned()]).is_ok());
+        assert_eq!(config.load().listen.as_deref(), Some("127.0.0.1:3001"));
+
+        for path in [valid, invalid_yaml, invalid_hash, modified] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
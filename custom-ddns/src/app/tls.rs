@@ -0,0 +1,107 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use axum_server::tls_rustls::RustlsConfig;
+use p12_keystore::KeyStore;
+
+use crate::config::TlsConfig;
+
+/// Certificate chain and private key of the HTTPS listener
+enum Identity {
+    Pem { certificate: Vec<u8>, key: Vec<u8> },
+    Der { chain: Vec<Vec<u8>>, key: Vec<u8> },
+}
+
+impl Identity {
+    fn read(tls: &TlsConfig) -> io::Result<Self> {
+        match (&tls.certificate, &tls.key, &tls.pkcs12) {
+            (Some(certificate), Some(key), _) => Ok(Identity::Pem {
+                certificate: fs::read(certificate)?,
+                key: fs::read(key)?,
+            }),
+            (_, _, Some(pkcs12)) => pkcs12_identity(&fs::read(pkcs12)?, &tls.pkcs12_password),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "tls requires a certificate and a key, or a pkcs12",
+            )),
+        }
+    }
+}
+
+/// Certificate chain and key of the first private key entry of a PKCS#12 archive
+fn pkcs12_identity(pkcs12: &[u8], password: &str) -> io::Result<Identity> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    let keystore = KeyStore::from_pkcs12(pkcs12, password).map_err(|e| invalid(e.to_string()))?;
+    let (_, chain) = keystore
+        .private_key_chain()
+        .ok_or(invalid("no private key in the pkcs12".to_owned()))?;
+
+    Ok(Identity::Der {
+        chain: chain
+            .chain()
+            .iter()
+            .map(|certificate| certificate.as_der().to_vec())
+            .collect(),
+        key: chain.key().to_vec(),
+    })
+}
+
+/// Rustls configuration of the HTTPS listener
+pub(crate) async fn rustls_config(tls: &TlsConfig) -> io::Result<RustlsConfig> {
+    match Identity::read(tls)? {
+        Identity::Pem { certificate, key } => RustlsConfig::from_pem(certificate, key).await,
+        Identity::Der { chain, key } => RustlsConfig::from_der(chain, key).await,
+    }
+}
+
+async fn reload(config: &RustlsConfig, tls: &TlsConfig) -> io::Result<()> {
+    match Identity::read(tls)? {
+        Identity::Pem { certificate, key } => config.reload_from_pem(certificate, key).await,
+        Identity::Der { chain, key } => config.reload_from_der(chain, key).await,
+    }
+}
+
+/// Reload the certificate when its files are modified (eg: renewed)
+///
+/// The previous certificate is kept if the new files can't be loaded.
+pub(crate) async fn watch(config: RustlsConfig, tls: TlsConfig) {
+    let files: Vec<PathBuf> = [&tls.certificate, &tls.key, &tls.pkcs12]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    let mut modified = last_modified(&files);
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(tls.reload_interval)).await;
+
+        let current = last_modified(&files);
+
+        if current == modified {
+            continue;
+        }
+
+        match reload(&config, &tls).await {
+            Ok(()) => {
+                tracing::info!("tls: certificate reloaded");
+                modified = current;
+            }
+            Err(e) => tracing::error!("tls: certificate not reloaded: {}", e),
+        }
+    }
+}
+
+/// Latest modification of the files
+fn last_modified(files: &[PathBuf]) -> Option<SystemTime> {
+    files.iter().filter_map(|file| modified(file)).max()
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
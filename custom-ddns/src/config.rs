@@ -1,42 +1,339 @@
-use std::env;
+use std::{env, fmt, net::IpAddr, path::PathBuf};
 
 use config::{Config, ConfigError, File, Map};
+use gandi_v5_livedns_api::{Token, TokenSource};
+use ipnet::IpNet;
 use serde::Deserialize;
 
+use crate::credentials::{self, User};
+
 #[derive(Deserialize)]
 pub(crate) struct AppConfig {
-    pub(crate) listen: String,
+    /// Address of the HTTP listener (optional with a HTTPS listener)
+    #[serde(default)]
+    pub(crate) listen: Option<String>,
+    /// HTTPS listener
+    #[serde(default)]
+    pub(crate) tls: Option<TlsConfig>,
     pub(crate) default_rrset_ttl: u32,
+    /// Proxies allowed to forward the address of clients (Forwarded and X-Forwarded-For headers)
+    #[serde(default)]
+    pub(crate) trusted_proxies: Vec<IpNet>,
+    /// Clients authenticated with a Basic authorization
+    #[serde(default)]
+    users: Vec<User>,
     whitelist: Map<String, Whitelist>,
+    #[serde(default)]
+    pub(crate) cache: CacheConfig,
+    #[serde(default)]
+    pub(crate) limits: LimitsConfig,
+    /// Reload the configuration when its files are modified (SIGHUP reloads it in any case)
+    #[serde(default)]
+    pub(crate) reload_on_change: bool,
+}
+
+/// HTTPS listener with a PEM certificate chain and key, or a PKCS#12 archive
+#[derive(Clone, Deserialize)]
+pub(crate) struct TlsConfig {
+    pub(crate) listen: String,
+    pub(crate) certificate: Option<PathBuf>,
+    pub(crate) key: Option<PathBuf>,
+    pub(crate) pkcs12: Option<PathBuf>,
+    #[serde(default)]
+    pub(crate) pkcs12_password: String,
+    /// Seconds between two checks of the files, to reload a renewed certificate
+    #[serde(default = "TlsConfig::default_reload_interval")]
+    pub(crate) reload_interval: u64,
+    /// Obtain and renew the certificate (stored in the certificate and key files)
+    #[serde(default)]
+    pub(crate) acme: Option<AcmeConfig>,
+}
+
+/// ACME account and domains of a certificate validated with DNS-01 challenges
+#[derive(Clone, Deserialize)]
+pub(crate) struct AcmeConfig {
+    /// Directory of the ACME server (eg: Let's Encrypt)
+    pub(crate) directory_url: String,
+    /// Contacts of the account (eg: `mailto:admin@example.org`)
+    #[serde(default)]
+    pub(crate) contact: Vec<String>,
+    /// Domains of the certificate, in the whitelist (eg: `ddns.example.org`)
+    pub(crate) domains: Vec<String>,
+    /// Private key of the account, generated if missing
+    pub(crate) account_key: PathBuf,
+    /// Days before the expiration to renew the certificate
+    #[serde(default = "AcmeConfig::default_renew_before")]
+    pub(crate) renew_before: u64,
+    /// Seconds to wait for the challenge records to be published
+    #[serde(default = "AcmeConfig::default_propagation_delay")]
+    pub(crate) propagation_delay: u64,
+    /// Additional root certificate of the ACME server (eg: Pebble)
+    pub(crate) ca_certificate: Option<PathBuf>,
+}
+
+impl AcmeConfig {
+    fn default_renew_before() -> u64 {
+        30
+    }
+
+    fn default_propagation_delay() -> u64 {
+        60
+    }
+}
+
+impl TlsConfig {
+    fn default_reload_interval() -> u64 {
+        60
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match (&self.certificate, &self.key, &self.pkcs12, &self.acme) {
+            (Some(_), Some(_), None, _) | (None, None, Some(_), None) => Ok(()),
+            (_, _, _, Some(_)) => {
+                Err("config: tls acme requires a certificate and a key file".to_owned())
+            }
+            _ => Err("config: tls requires a certificate and a key, or a pkcs12".to_owned()),
+        }
+    }
+}
+
+/// Cache of the records values, to not update a record already up to date
+#[derive(Deserialize)]
+#[serde(default)]
+pub(crate) struct CacheConfig {
+    /// Optional file keeping the cache across restarts
+    pub(crate) state_file: Option<PathBuf>,
+    /// Seconds before reading a cached record again from Gandi
+    pub(crate) refresh_interval: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            state_file: None,
+            refresh_interval: 3600,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct Whitelist {
-    personal_access_token: String,
+    personal_access_token: PersonalAccessToken,
     records: Vec<Record>,
+    /// Deprecated: literal `Basic base64(user:pass)` headers, use users with grants
+    #[serde(default)]
     authorizations: Vec<String>,
 }
 
+/// Rate limits and brute-force protection (0 disables a limit)
+#[derive(Deserialize)]
+#[serde(default)]
+pub(crate) struct LimitsConfig {
+    /// Requests per minute of a client address
+    pub(crate) requests_per_minute_per_ip: u32,
+    /// Requests per minute of a verified credential (username of the Basic authorization)
+    pub(crate) requests_per_minute_per_credential: u32,
+    /// Failed authentications before a client address is locked out
+    pub(crate) max_failed_authentications: u32,
+    /// Seconds of a lockout
+    pub(crate) lockout: u64,
+    /// Minimum seconds between two updates of the same record
+    pub(crate) min_update_interval: u64,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            requests_per_minute_per_ip: 60,
+            requests_per_minute_per_credential: 30,
+            max_failed_authentications: 5,
+            lockout: 900,
+            min_update_interval: 60,
+        }
+    }
+}
+
+impl Whitelist {
+    fn record(&self, rrset_name: &str, rrset_type: &str) -> Option<&Record> {
+        self.records
+            .iter()
+            .find(|record| record.rrset_name == rrset_name && record.rrset_type == rrset_type)
+    }
+}
+
+/// The token itself or where to find it (env, file, command or keyring)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PersonalAccessToken {
+    Token(Token),
+    Source(TokenSource),
+}
+
 #[derive(Deserialize)]
 struct Record {
     rrset_name: String,
     rrset_type: String,
     rrset_ttl_max: u32,
+    /// Allow private addresses (eg: 192.168.0.0/16, fc00::/7)
+    #[serde(default)]
+    allow_private: bool,
+    /// Allow loopback addresses (127.0.0.0/8, ::1)
+    #[serde(default)]
+    allow_loopback: bool,
+    /// Networks the address must belong to (any network when empty)
+    #[serde(default)]
+    allowed_cidrs: Vec<IpNet>,
+}
+
+impl Record {
+    /// Returns the reason why the address can't be set to the record
+    fn refuse(&self, ip: &IpAddr) -> Option<&'static str> {
+        if ip.is_unspecified() || ip.is_multicast() || is_broadcast(ip) {
+            Some("not a host address")
+        } else if ip.is_loopback() && !self.allow_loopback {
+            Some("a loopback address")
+        } else if is_private(ip) && !self.allow_private {
+            Some("a private address")
+        } else if !self.allowed_cidrs.is_empty()
+            && !self.allowed_cidrs.iter().any(|cidr| cidr.contains(ip))
+        {
+            Some("not in the allowed networks")
+        } else {
+            None
+        }
+    }
+}
+
+fn is_broadcast(ip: &IpAddr) -> bool {
+    matches!(ip, IpAddr::V4(ip) if ip.is_broadcast())
+}
+
+/// Private, shared (100.64.0.0/10), link-local and unique local addresses
+fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private()
+                || ip.is_link_local()
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => {
+            (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// An update allowed by the configuration
+pub(crate) struct Authorized {
+    /// Username of the client (or `legacy authorization`)
+    pub(crate) client: String,
+    pub(crate) personal_access_token: Token,
+}
+
+/// Why an update of a record is refused
+#[derive(Debug, PartialEq)]
+pub(crate) enum Denied {
+    /// The domain or the record isn't whitelisted
+    NotWhitelisted(String),
+    /// The authorization isn't allowed to update the domain
+    Forbidden(String),
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Denied::NotWhitelisted(reason) | Denied::Forbidden(reason) => write!(f, "{}", reason),
+        }
+    }
 }
 
 impl AppConfig {
     pub(crate) fn build() -> Result<Self, ConfigError> {
-        let cddns_profile = env::var("CDDNS_PROFILE").unwrap_or("prod".to_owned());
+        Self::build_from(&Self::files())
+    }
 
-        Config::builder()
-            .add_source(File::with_name(&format!("{}.yaml", cddns_profile)).required(false))
-            .add_source(File::with_name("local.yaml").required(false))
+    /// Build and verify the configuration of files, missing files are ignored
+    pub(crate) fn build_from(files: &[PathBuf]) -> Result<Self, ConfigError> {
+        let mut config: AppConfig = files
+            .iter()
+            .fold(Config::builder(), |builder, file| {
+                builder.add_source(File::from(file.as_path()).required(false))
+            })
             .build()?
-            .try_deserialize()
+            .try_deserialize()?;
+
+        config.validate()?;
+        config.resolve_tokens()?;
+
+        Ok(config)
+    }
+
+    /// Files of the configuration: `$CDDNS_PROFILE.yaml` (default: prod.yaml) then local.yaml
+    pub(crate) fn files() -> Vec<PathBuf> {
+        let cddns_profile = env::var("CDDNS_PROFILE").unwrap_or("prod".to_owned());
+
+        vec![
+            PathBuf::from(format!("{}.yaml", cddns_profile)),
+            PathBuf::from("local.yaml"),
+        ]
+    }
+
+    /// Verify listeners, password hashes and grants of users, warn about deprecated authorizations
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.listen.is_none() && self.tls.is_none() {
+            return Err(ConfigError::Message(
+                "config: listen or tls is required".to_owned(),
+            ));
+        }
+
+        if let Some(tls) = &self.tls {
+            tls.validate().map_err(ConfigError::Message)?;
+
+            for domain in tls.acme.iter().flat_map(|acme| &acme.domains) {
+                if self
+                    .split_hostname(domain.trim_start_matches("*."))
+                    .is_none()
+                {
+                    return Err(ConfigError::Message(format!(
+                        "config: acme domain {} is not in the whitelist",
+                        domain
+                    )));
+                }
+            }
+        }
+
+        for user in &self.users {
+            user.validate().map_err(ConfigError::Message)?;
+        }
+
+        for (fqdn, whitelist) in &self.whitelist {
+            if !whitelist.authorizations.is_empty() {
+                tracing::warn!(
+                    "config: whitelist {}: authorizations are deprecated, use users with a password hash (custom-ddns hash-password)",
+                    fqdn
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read every personal access token from its source
+    fn resolve_tokens(&mut self) -> Result<(), ConfigError> {
+        for (fqdn, whitelist) in self.whitelist.iter_mut() {
+            if let PersonalAccessToken::Source(source) = &whitelist.personal_access_token {
+                let token = source.token().map_err(|e| {
+                    ConfigError::Message(format!("config: whitelist {}: {}", fqdn, e))
+                })?;
+
+                whitelist.personal_access_token = PersonalAccessToken::Token(token);
+            }
+        }
+
+        Ok(())
     }
 
     /// Returns a personal access token if the record to update
-    /// is whitelisted and authorized to be updated by the authorization
+    /// is whitelisted and granted to the client of the authorization
     pub(crate) fn get_pat_if_authorized(
         &self,
         fqdn: &str,
@@ -44,32 +341,306 @@ impl AppConfig {
         rrset_type: &str,
         rrset_ttl: u32,
         authorization: &str,
-    ) -> Result<String, String> {
+    ) -> Result<Authorized, Denied> {
         let whitelist = self
             .whitelist
             .get(fqdn)
-            .ok_or(format!("config: fqdn {} is not whitelisted", fqdn))?;
+            .ok_or(Denied::NotWhitelisted(format!(
+                "config: fqdn {} is not whitelisted",
+                fqdn
+            )))?;
 
         _ = whitelist
-            .records
-            .iter()
-            .find(|record| {
-                record.rrset_name == rrset_name
-                    && record.rrset_type == rrset_type
-                    && rrset_ttl <= record.rrset_ttl_max
-            })
-            .ok_or(format!(
+            .record(rrset_name, rrset_type)
+            .filter(|record| rrset_ttl <= record.rrset_ttl_max)
+            .ok_or(Denied::NotWhitelisted(format!(
                 "config: record {}.{} type {} with ttl {} is not whitelisted",
                 rrset_name, fqdn, rrset_type, rrset_ttl
-            ))?;
+            )))?;
 
-        if whitelist.authorizations.contains(&authorization.to_owned()) {
-            Ok(whitelist.personal_access_token.to_owned())
+        if let Some(client) =
+            self.authenticate(fqdn, rrset_name, rrset_type, whitelist, authorization)
+        {
+            match &whitelist.personal_access_token {
+                PersonalAccessToken::Token(token) => Ok(Authorized {
+                    client,
+                    personal_access_token: token.clone(),
+                }),
+                PersonalAccessToken::Source(source) => Err(Denied::Forbidden(format!(
+                    "config: personal access token of {} not resolved ({})",
+                    fqdn, source
+                ))),
+            }
         } else {
-            Err(format!(
+            Err(Denied::Forbidden(format!(
                 "config: access forbidden to update record {}.{} type {}",
                 rrset_name, fqdn, rrset_type
-            ))
+            )))
+        }
+    }
+
+    /// Returns the client of the authorization if it's a user granted to update the record
+    /// (or a deprecated authorization of the whitelist)
+    fn authenticate(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+        whitelist: &Whitelist,
+        authorization: &str,
+    ) -> Option<String> {
+        let user = credentials::basic(authorization).and_then(|(username, password)| {
+            let user = self.users.iter().find(|user| {
+                user.username == username && user.granted(fqdn, rrset_name, rrset_type)
+            });
+
+            credentials::verify(user, &password).then_some(username)
+        });
+
+        let legacy = credentials::legacy_authorized(&whitelist.authorizations, authorization);
+
+        user.or(legacy.then(|| "legacy authorization".to_owned()))
+    }
+
+    /// Returns an error if the address isn't allowed by the policy of the whitelisted record
+    pub(crate) fn check_address(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+        ip: &IpAddr,
+    ) -> Result<(), String> {
+        let record = self
+            .whitelist
+            .get(fqdn)
+            .and_then(|whitelist| whitelist.record(rrset_name, rrset_type))
+            .ok_or(format!(
+                "config: record {}.{} type {} is not whitelisted",
+                rrset_name, fqdn, rrset_type
+            ))?;
+
+        match record.refuse(ip) {
+            Some(reason) => Err(format!(
+                "Ip {} can't be set to record {}.{} type {}: {}",
+                ip, rrset_name, fqdn, rrset_type, reason
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Personal access token of a whitelisted domain
+    pub(crate) fn personal_access_token(&self, fqdn: &str) -> Option<&Token> {
+        match &self.whitelist.get(fqdn)?.personal_access_token {
+            PersonalAccessToken::Token(token) => Some(token),
+            PersonalAccessToken::Source(_) => None,
         }
     }
+
+    /// Split a hostname into a whitelisted domain and a rrset name ('@' for the domain itself)
+    ///
+    /// The longest whitelisted domain is used when domains are nested.
+    pub(crate) fn split_hostname(&self, hostname: &str) -> Option<(String, String)> {
+        let hostname = hostname.trim_end_matches('.').to_lowercase();
+
+        self.whitelist
+            .keys()
+            .filter_map(|fqdn| {
+                if hostname == *fqdn {
+                    Some((fqdn.to_owned(), "@".to_owned()))
+                } else {
+                    hostname
+                        .strip_suffix(&format!(".{}", fqdn))
+                        .map(|rrset_name| (fqdn.to_owned(), rrset_name.to_owned()))
+                }
+            })
+            .max_by_key(|(fqdn, _)| fqdn.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::FileFormat;
+
+    fn app_config(yaml: &str) -> AppConfig {
+        Config::builder()
+            .add_source(File::from_str(yaml, FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    const CONFIG: &str = r#"
+listen: '127.0.0.1:3000'
+default_rrset_ttl: 300
+users:
+- username: router
+  # 1234
+  password_hash: '$argon2id$v=19$m=19456,t=2,p=1$n1BPN42gNM6jzA4VjgLkiQ$rSmRhZP0Kd3rkGdl6ILzyo1yV8A7Z72yMk0BS59d8dQ'
+  grants:
+  - fqdn: example.org
+    rrset_name: home
+    rrset_type: '*'
+whitelist:
+  example.org:
+    personal_access_token: TOKEN
+    records:
+    - rrset_name: home
+      rrset_type: A
+      rrset_ttl_max: 10800
+    - rrset_name: office
+      rrset_type: A
+      rrset_ttl_max: 10800
+      allow_private: true
+      allowed_cidrs: ['192.168.1.0/24', '198.51.100.0/24']
+    authorizations:
+    - Basic dGVzdDoxMjM0
+  lab.example.org:
+    personal_access_token: TOKEN
+    records: []
+    authorizations: []
+"#;
+
+    #[test]
+    fn split_hostname() {
+        let config = app_config(CONFIG);
+
+        assert_eq!(
+            config.split_hostname("Home.Example.org."),
+            Some(("example.org".to_owned(), "home".to_owned()))
+        );
+        assert_eq!(
+            config.split_hostname("nas.lab.example.org"),
+            Some(("lab.example.org".to_owned(), "nas".to_owned()))
+        );
+        assert_eq!(
+            config.split_hostname("example.org"),
+            Some(("example.org".to_owned(), "@".to_owned()))
+        );
+        assert_eq!(config.split_hostname("home.example.net"), None);
+        assert_eq!(config.split_hostname("notexample.org"), None);
+    }
+
+    #[test]
+    fn denied_updates() {
+        let config = app_config(CONFIG);
+
+        assert!(config
+            .get_pat_if_authorized("example.org", "home", "A", 300, "Basic dGVzdDoxMjM0")
+            .is_ok());
+        assert!(matches!(
+            config.get_pat_if_authorized("example.org", "nas", "A", 300, "Basic dGVzdDoxMjM0"),
+            Err(Denied::NotWhitelisted(_))
+        ));
+        assert!(matches!(
+            config.get_pat_if_authorized("example.org", "home", "A", 300, "Basic other"),
+            Err(Denied::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn users() {
+        let config = app_config(CONFIG);
+        assert!(config.validate().is_ok());
+
+        // router:1234 and router:12345
+        let authorized = config
+            .get_pat_if_authorized("example.org", "home", "A", 300, "Basic cm91dGVyOjEyMzQ=")
+            .unwrap();
+        assert_eq!(authorized.client, "router");
+        assert!(matches!(
+            config.get_pat_if_authorized("example.org", "home", "A", 300, "Basic cm91dGVyOjEyMzQ1"),
+            Err(Denied::Forbidden(_))
+        ));
+
+        // whitelisted but not granted
+        assert!(matches!(
+            config.get_pat_if_authorized(
+                "example.org",
+                "office",
+                "A",
+                300,
+                "Basic cm91dGVyOjEyMzQ="
+            ),
+            Err(Denied::Forbidden(_))
+        ));
+
+        let config = app_config(&CONFIG.replace(
+            "rrset_name: home\n    rrset_type",
+            "rrset_name: '[home'\n    rrset_type",
+        ));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn listeners() {
+        let https_only = CONFIG.replace(
+            "listen: '127.0.0.1:3000'",
+            "tls:\n  listen: '127.0.0.1:3443'\n  pkcs12: cddns.p12",
+        );
+        let config = app_config(&https_only);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.listen, None);
+        assert_eq!(config.tls.map(|tls| tls.reload_interval), Some(60));
+
+        let config = app_config(&https_only.replace("pkcs12: cddns.p12", "key: key.pem"));
+        assert!(config.validate().is_err());
+
+        let config = app_config(&CONFIG.replace("listen: '127.0.0.1:3000'", ""));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn acme_domains() {
+        let acme = |domain: &str| {
+            CONFIG.replace(
+                "listen: '127.0.0.1:3000'",
+                &format!("tls:\n  listen: '127.0.0.1:3443'\n  certificate: cert.pem\n  key: key.pem\n  acme:\n    directory_url: https://localhost:14000/dir\n    account_key: account.pem\n    domains: ['{}']", domain),
+            )
+        };
+
+        let config = app_config(&acme("*.lab.example.org"));
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config
+                .tls
+                .and_then(|tls| tls.acme)
+                .map(|acme| acme.renew_before),
+            Some(30)
+        );
+
+        assert!(app_config(&acme("ddns.example.net")).validate().is_err());
+        assert!(
+            app_config(&acme("ddns.example.org").replace("  key: key.pem\n", ""))
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn address_policy() {
+        let config = app_config(CONFIG);
+        let check = |rrset_name: &str, ip: &str| {
+            config.check_address("example.org", rrset_name, "A", &ip.parse().unwrap())
+        };
+
+        assert!(check("home", "198.51.100.7").is_ok());
+        assert!(check("home", "192.168.1.10").is_err());
+        assert!(check("home", "100.64.0.1").is_err());
+        assert!(check("home", "127.0.0.1").is_err());
+        assert!(check("home", "0.0.0.0").is_err());
+        assert!(check("home", "255.255.255.255").is_err());
+
+        assert!(check("office", "192.168.1.10").is_ok());
+        assert!(check("office", "198.51.100.7").is_ok());
+        assert!(check("office", "192.168.2.10").is_err());
+        assert!(check("office", "203.0.113.1").is_err());
+
+        assert!(check("nas", "198.51.100.7").is_err());
+
+        assert!(is_private(&"fd00::1".parse().unwrap()));
+        assert!(is_private(&"fe80::1".parse().unwrap()));
+        assert!(!is_private(&"2001:db8::1".parse().unwrap()));
+    }
 }
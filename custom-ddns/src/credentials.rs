@@ -0,0 +1,211 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, IsTerminal, Write},
+    sync::OnceLock,
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use glob::Pattern;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+
+/// A client of the service (eg: a router)
+#[derive(Deserialize)]
+pub(crate) struct User {
+    pub(crate) username: String,
+    /// Argon2 hash of the password (PHC string format, see `custom-ddns hash-password`)
+    password_hash: String,
+    /// Records the user can update
+    #[serde(default)]
+    grants: Vec<Grant>,
+}
+
+/// Records granted to a user (glob patterns, eg: `*.site1`)
+#[derive(Deserialize)]
+pub(crate) struct Grant {
+    fqdn: String,
+    rrset_name: String,
+    rrset_type: String,
+}
+
+impl User {
+    /// Returns an error if the password hash or a pattern of the grants can't be parsed
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        PasswordHash::new(&self.password_hash)
+            .map_err(|e| format!("config: password hash of user {}: {}", self.username, e))?;
+
+        for grant in &self.grants {
+            for pattern in [&grant.fqdn, &grant.rrset_name, &grant.rrset_type] {
+                Pattern::new(pattern).map_err(|e| {
+                    format!("config: grant {} of user {}: {}", pattern, self.username, e)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if a grant of the user matches the record
+    pub(crate) fn granted(&self, fqdn: &str, rrset_name: &str, rrset_type: &str) -> bool {
+        let matches =
+            |pattern: &str, value: &str| Pattern::new(pattern).is_ok_and(|p| p.matches(value));
+
+        self.grants.iter().any(|grant| {
+            matches(&grant.fqdn, fqdn)
+                && matches(&grant.rrset_name, rrset_name)
+                && matches(&grant.rrset_type, rrset_type)
+        })
+    }
+}
+
+/// Username and password of a Basic authorization header
+pub(crate) fn basic(authorization: &str) -> Option<(String, String)> {
+    let (scheme, credentials) = authorization.trim().split_once(' ')?;
+
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    let credentials = String::from_utf8(STANDARD.decode(credentials.trim()).ok()?).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+
+    Some((username.to_owned(), password.to_owned()))
+}
+
+/// Returns true if the password is the password of the user
+///
+/// An unknown user is verified against a dummy hash to not disclose which users exist.
+pub(crate) fn verify(user: Option<&User>, password: &str) -> bool {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+
+    let (password_hash, known) = match user {
+        Some(user) => (user.password_hash.as_str(), true),
+        None => (
+            DUMMY
+                .get_or_init(|| hash_password("dummy").unwrap_or_default())
+                .as_str(),
+            false,
+        ),
+    };
+
+    let verified = PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false);
+
+    verified && known
+}
+
+/// Returns true if the authorization header is in the list, in constant time
+pub(crate) fn legacy_authorized(authorizations: &[String], authorization: &str) -> bool {
+    authorizations.iter().fold(false, |authorized, legacy| {
+        authorized | bool::from(legacy.as_bytes().ct_eq(authorization.as_bytes()))
+    })
+}
+
+/// Argon2id hash of a password with a random salt
+pub(crate) fn hash_password(password: &str) -> Result<String, Box<dyn Error>> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| e.to_string())?
+        .to_string())
+}
+
+/// Read a password on stdin and print its hash
+pub(crate) fn hash_password_command() -> Result<(), Box<dyn Error>> {
+    let stdin = io::stdin();
+
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+        io::stderr().flush()?;
+    }
+
+    let mut password = String::new();
+    stdin.lock().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    if password.is_empty() {
+        return Err("hash-password: empty password".into());
+    }
+
+    println!("{}", hash_password(password)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_authorization() {
+        assert_eq!(
+            basic("Basic dGVzdDoxMjM0"),
+            Some(("test".to_owned(), "1234".to_owned()))
+        );
+        assert_eq!(basic("Bearer dGVzdDoxMjM0"), None);
+        assert_eq!(basic("Basic !!!"), None);
+    }
+
+    #[test]
+    fn verify_password() {
+        let user = User {
+            username: "test".to_owned(),
+            password_hash: hash_password("1234").unwrap(),
+            grants: vec![],
+        };
+
+        assert!(user.validate().is_ok());
+        assert!(verify(Some(&user), "1234"));
+        assert!(!verify(Some(&user), "12345"));
+        assert!(!verify(None, "dummy"));
+    }
+
+    #[test]
+    fn grants() {
+        let grant = |fqdn: &str, rrset_name: &str, rrset_type: &str| Grant {
+            fqdn: fqdn.to_owned(),
+            rrset_name: rrset_name.to_owned(),
+            rrset_type: rrset_type.to_owned(),
+        };
+
+        let user = User {
+            username: "site1".to_owned(),
+            password_hash: String::new(),
+            grants: vec![
+                grant("example.org", "*.site1", "*"),
+                grant("example.net", "home", "A"),
+            ],
+        };
+
+        assert!(user.granted("example.org", "nas.site1", "AAAA"));
+        assert!(user.granted("example.net", "home", "A"));
+        assert!(!user.granted("example.org", "nas.site2", "A"));
+        assert!(!user.granted("example.net", "home", "AAAA"));
+
+        let user = User {
+            grants: vec![grant("example.org", "[site", "A")],
+            ..user
+        };
+
+        assert!(user.validate().is_err());
+    }
+
+    #[test]
+    fn legacy_authorizations() {
+        let authorizations = vec!["Basic dGVzdDoxMjM0".to_owned()];
+
+        assert!(legacy_authorized(&authorizations, "Basic dGVzdDoxMjM0"));
+        assert!(!legacy_authorized(&authorizations, "Basic dGVzdDoxMjM1"));
+        assert!(!legacy_authorized(&[], "Basic dGVzdDoxMjM0"));
+    }
+}
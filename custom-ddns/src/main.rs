@@ -5,12 +5,17 @@
 
 mod app;
 mod config;
+mod credentials;
 
-use std::process::ExitCode;
+use std::{env, process::ExitCode};
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    let terminated = app::run_app().await;
+    let terminated = match env::args().nth(1).as_deref() {
+        None => app::run_app().await,
+        Some("hash-password") => credentials::hash_password_command(),
+        Some(command) => Err(format!("unknown command {} (hash-password)", command).into()),
+    };
 
     match terminated {
         Ok(_) => ExitCode::SUCCESS,
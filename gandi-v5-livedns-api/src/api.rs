@@ -2,8 +2,10 @@
 
 pub mod domains;
 pub mod records;
+pub mod search;
 
-use crate::engine::{Endpoint, Engine};
+use crate::engine::{Endpoint, Engine, PlannedRequest};
+use reqwest::StatusCode;
 use std::error::Error;
 
 /// The Api entrypoint
@@ -65,6 +67,54 @@ impl Api {
 
         Ok(Api { engine })
     }
+
+    /// Returns the [`Api`] acting on behalf of an organization.
+    ///
+    /// Every query will be sent with the `sharing_id` query parameter.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let api = Api::build(Endpoint::Prod, "token")?.with_sharing_id("organization-id");
+    /// ```
+    pub fn with_sharing_id(mut self, sharing_id: &str) -> Self {
+        self.engine.set_sharing_id(sharing_id);
+
+        self
+    }
+
+    /// Returns the [`Api`] in dry run mode.
+    ///
+    /// Queries reading resources are sent but queries creating, updating
+    /// or deleting resources are only recorded (see [`Api::planned_requests`]).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let api = Api::build(Endpoint::Prod, "token")?.with_dry_run();
+    ///
+    /// api.delete_record_by_name_and_type("example.org", "test", "A").await?;
+    ///
+    /// println!("{:?}", api.planned_requests());
+    /// ```
+    pub fn with_dry_run(mut self) -> Self {
+        self.engine.set_dry_run();
+
+        self
+    }
+
+    /// Requests that would have been sent in dry run mode, in order.
+    pub fn planned_requests(&self) -> Vec<PlannedRequest> {
+        self.engine.planned_requests()
+    }
+}
+
+/// Returns true if the error is a 404 Not Found returned by Gandi Api.
+pub(crate) fn is_not_found(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        == Some(StatusCode::NOT_FOUND)
 }
 
 #[cfg(test)]
@@ -77,4 +127,11 @@ mod tests {
 
         assert!(api.is_ok());
     }
+
+    #[test]
+    fn build_api_without_dry_run() {
+        let api = Api::build(crate::Endpoint::Prod, "token").unwrap();
+
+        assert!(api.planned_requests().is_empty());
+    }
 }
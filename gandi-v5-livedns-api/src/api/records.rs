@@ -2,11 +2,11 @@
 
 use std::error::Error;
 
-use crate::api::Api;
+use crate::api::{is_not_found, Api};
 use serde::{Deserialize, Serialize};
 
 /// Type representing a record
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Record {
     /// Name of the record
     pub rrset_name: String,
@@ -25,7 +25,7 @@ pub struct Record {
 /// ```no_run
 /// let record = UpsertRecord { rrset_values: vec!["127.0.0.1".to_owned()], rrset_ttl: Some(300) };
 /// ```
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct UpsertRecord {
     /// A list of values for this record
     pub rrset_values: Vec<String>,
@@ -33,6 +33,97 @@ pub struct UpsertRecord {
     pub rrset_ttl: Option<u32>,
 }
 
+/// Type used to add and remove values of a single record
+///
+/// # Examples:
+///
+/// ```no_run
+/// let patch = PatchRecord { add_values: vec!["10.0.0.3".to_owned()], remove_values: vec!["10.0.0.1".to_owned()], rrset_ttl: None };
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct PatchRecord {
+    /// Values to add if missing
+    pub add_values: Vec<String>,
+    /// Values to remove if present
+    pub remove_values: Vec<String>,
+    /// The time in seconds that DNS resolvers should cache this record (current ttl if not provided)
+    pub rrset_ttl: Option<u32>,
+}
+
+/// Result of a [`PatchRecord`] on a record
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchOutcome {
+    /// The record is already as expected
+    Unchanged,
+    /// The record is created or overwritten
+    Upserted(UpsertRecord),
+    /// The record is deleted as no value remains
+    Deleted,
+}
+
+impl PatchRecord {
+    /// Returns the outcome of the patch on the current record (`None` if the record doesn't exist)
+    ///
+    /// Values keep their order, added values are appended.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// let patch = PatchRecord { add_values: vec!["10.0.0.2".to_owned()], ..Default::default() };
+    ///
+    /// match patch.merge(current.as_ref()) {
+    ///     PatchOutcome::Upserted(record) => println!("{:?}", record.rrset_values),
+    ///     _ => {}
+    /// }
+    /// ```
+    pub fn merge(&self, current: Option<&Record>) -> PatchOutcome {
+        let current_values = current
+            .map(|record| record.rrset_values.as_slice())
+            .unwrap_or_default();
+        let current_ttl = current.and_then(|record| record.rrset_ttl);
+
+        let rrset_values = add_values(
+            &remove_values(current_values, &self.remove_values),
+            &self.add_values,
+        );
+        let rrset_ttl = self.rrset_ttl.or(current_ttl);
+
+        match (current, rrset_values.is_empty()) {
+            (None, true) => PatchOutcome::Unchanged,
+            (Some(_), true) => PatchOutcome::Deleted,
+            (Some(_), false) if rrset_values == current_values && rrset_ttl == current_ttl => {
+                PatchOutcome::Unchanged
+            }
+            _ => PatchOutcome::Upserted(UpsertRecord {
+                rrset_values,
+                rrset_ttl,
+            }),
+        }
+    }
+}
+
+/// Returns the values with the missing ones appended
+pub fn add_values(values: &[String], add: &[String]) -> Vec<String> {
+    let mut values = values.to_vec();
+
+    for value in add {
+        if !values.contains(value) {
+            values.push(value.to_owned());
+        }
+    }
+
+    values
+}
+
+/// Returns the values without the removed ones
+pub fn remove_values(values: &[String], remove: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .filter(|value| !remove.contains(value))
+        .cloned()
+        .collect()
+}
+
 impl Api {
     /// List records associated with a domain
     ///
@@ -105,6 +196,36 @@ impl Api {
             .await
     }
 
+    /// Get a single record with its name and type, `None` if the record doesn't exist
+    ///
+    /// GET on <https://api.gandi.net/v5/livedns/domains/{fqdn}/records/{rrset_name}/{rrset_type}>
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// let api = Api::build(Endpoint::Prod, "token")?;
+    ///
+    /// match api.find_record_by_name_and_type("example.org", "test", "TXT").await? {
+    ///     Some(record) => println!("{:?}", record),
+    ///     None => println!("test.example.org type TXT doesn't exist"),
+    /// }
+    /// ```
+    pub async fn find_record_by_name_and_type(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+    ) -> Result<Option<Record>, Box<dyn Error>> {
+        match self
+            .record_by_name_and_type(fqdn, rrset_name, rrset_type)
+            .await
+        {
+            Ok(record) => Ok(Some(record)),
+            Err(e) if is_not_found(e.as_ref()) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Create a new record whose name and type are defined by the path
     ///
     /// POST on <https://api.gandi.net/v5/livedns/domains/{fqdn}/records/{rrset_name}/{rrset_type}>
@@ -165,6 +286,49 @@ impl Api {
         Ok(self.engine.put(&url, body).await?)
     }
 
+    /// Add and remove values of a single record with {rrset_name} and {rrset_type}
+    ///
+    /// The record is created if it doesn't exist and deleted when no value remains.
+    ///
+    /// GET then PUT or DELETE on <https://api.gandi.net/v5/livedns/domains/{fqdn}/records/{rrset_name}/{rrset_type}>
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// let api = Api::build(Endpoint::Prod, "token")?;
+    ///
+    /// // add a verification token to TXT records of example.org
+    /// let patch = PatchRecord { add_values: vec!["\"token\"".to_owned()], ..Default::default() };
+    /// api.patch_record_by_name_and_type("example.org", "@", "TXT", &patch).await?;
+    /// ```
+    pub async fn patch_record_by_name_and_type(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+        patch: &PatchRecord,
+    ) -> Result<PatchOutcome, Box<dyn Error>> {
+        let current = self
+            .find_record_by_name_and_type(fqdn, rrset_name, rrset_type)
+            .await?;
+
+        let outcome = patch.merge(current.as_ref());
+
+        match &outcome {
+            PatchOutcome::Unchanged => {}
+            PatchOutcome::Upserted(record) => {
+                self.upsert_record_by_name_and_type(fqdn, rrset_name, rrset_type, record)
+                    .await?
+            }
+            PatchOutcome::Deleted => {
+                self.delete_record_by_name_and_type(fqdn, rrset_name, rrset_type)
+                    .await?
+            }
+        }
+
+        Ok(outcome)
+    }
+
     /// Delete record with {rrset_name} and {rrset_type}
     ///
     /// DELETE on <https://api.gandi.net/v5/livedns/domains/{fqdn}/records/{rrset_name}/{rrset_type}>
@@ -194,9 +358,128 @@ impl Api {
 
 #[cfg(test)]
 mod tests {
-    use crate::{records::UpsertRecord, Api};
+    use crate::{
+        records::{PatchOutcome, PatchRecord, Record, UpsertRecord},
+        Api, PlannedRequest,
+    };
     use std::env;
 
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn record(rrset_values: &[&str]) -> Record {
+        Record {
+            rrset_name: "test".to_owned(),
+            rrset_type: "A".to_owned(),
+            rrset_values: values(rrset_values),
+            rrset_ttl: Some(300),
+        }
+    }
+
+    #[test]
+    fn patch_merge() {
+        let patch = PatchRecord {
+            add_values: values(&["10.0.0.3", "10.0.0.2"]),
+            remove_values: values(&["10.0.0.1"]),
+            rrset_ttl: None,
+        };
+
+        assert_eq!(
+            patch.merge(Some(&record(&["10.0.0.1", "10.0.0.2"]))),
+            PatchOutcome::Upserted(UpsertRecord {
+                rrset_values: values(&["10.0.0.2", "10.0.0.3"]),
+                rrset_ttl: Some(300),
+            })
+        );
+        assert_eq!(
+            patch.merge(Some(&record(&["10.0.0.2", "10.0.0.3"]))),
+            PatchOutcome::Unchanged
+        );
+        assert_eq!(
+            patch.merge(None),
+            PatchOutcome::Upserted(UpsertRecord {
+                rrset_values: values(&["10.0.0.3", "10.0.0.2"]),
+                rrset_ttl: None,
+            })
+        );
+    }
+
+    #[test]
+    fn patch_merge_ttl_and_delete() {
+        let patch = PatchRecord {
+            rrset_ttl: Some(600),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            patch.merge(Some(&record(&["10.0.0.1"]))),
+            PatchOutcome::Upserted(UpsertRecord {
+                rrset_values: values(&["10.0.0.1"]),
+                rrset_ttl: Some(600),
+            })
+        );
+
+        let patch = PatchRecord {
+            remove_values: values(&["10.0.0.1"]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            patch.merge(Some(&record(&["10.0.0.1"]))),
+            PatchOutcome::Deleted
+        );
+        assert_eq!(patch.merge(None), PatchOutcome::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn upsert_record_by_name_and_type_dry_run() {
+        let api = Api::build(
+            crate::Endpoint::Custom("http://127.0.0.1:9".to_owned()),
+            "token",
+        )
+        .unwrap()
+        .with_dry_run();
+
+        let record = UpsertRecord {
+            rrset_values: vec!["127.0.0.1".to_owned()],
+            rrset_ttl: Some(300),
+        };
+        let res = api
+            .upsert_record_by_name_and_type("example.org", "test", "A", &record)
+            .await;
+
+        assert!(res.is_ok());
+
+        assert_eq!(
+            api.planned_requests(),
+            vec![PlannedRequest {
+                method: "PUT".to_owned(),
+                url: "http://127.0.0.1:9/livedns/domains/example.org/records/test/A".to_owned(),
+                body: Some(r#"{"rrset_values":["127.0.0.1"],"rrset_ttl":300}"#.to_owned()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn find_record_by_name_and_type_none() {
+        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
+
+        let api = Api::build(crate::Endpoint::Sandbox, &pat);
+
+        assert!(api.is_ok());
+
+        let api = api.unwrap();
+
+        let res = api
+            .find_record_by_name_and_type("pygoscelis-sandbox.org", "test", "A")
+            .await;
+
+        assert!(res.is_ok());
+
+        assert!(res.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn records_404() {
         let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
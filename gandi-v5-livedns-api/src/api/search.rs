@@ -0,0 +1,143 @@
+//! Search records across every domain
+
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::api::{records::Record, Api};
+
+/// Type representing a value of a record found by a search
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordMatch {
+    /// Domain name
+    pub fqdn: String,
+    /// Name of the record
+    pub rrset_name: String,
+    /// Type of the record
+    pub rrset_type: String,
+    /// The value matching the search
+    pub rrset_value: String,
+    /// The time in seconds that DNS resolvers should cache this record
+    pub rrset_ttl: Option<u32>,
+}
+
+impl Api {
+    /// Search values of records across every domain
+    ///
+    /// Records of up to `concurrency` domains are queried at the same time.
+    /// `matches` is called with the fqdn, the record and one of its values.
+    ///
+    /// GET on <https://api.gandi.net/v5/livedns/domains> then on <https://api.gandi.net/v5/livedns/domains/{fqdn}/records>
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// let api = Api::build(Endpoint::Prod, "token")?;
+    ///
+    /// // where is 192.0.2.1 referenced?
+    /// let matches = api
+    ///     .search_records(8, |_, record, value| record.rrset_type == "A" && value == "192.0.2.1")
+    ///     .await?;
+    ///
+    /// println!("{:?}", matches);
+    /// ```
+    pub async fn search_records<F>(
+        &self,
+        concurrency: usize,
+        matches: F,
+    ) -> Result<Vec<RecordMatch>, Box<dyn Error>>
+    where
+        F: Fn(&str, &Record, &str) -> bool,
+    {
+        let domains = self.domains().await?;
+        let matches = &matches;
+
+        let results: Vec<Vec<RecordMatch>> = stream::iter(domains)
+            .map(|domain| async move {
+                let records = self.records(&domain.fqdn).await?;
+
+                Ok::<_, Box<dyn Error>>(matching_values(&domain.fqdn, &records, matches))
+            })
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        Ok(results.into_iter().flatten().collect())
+    }
+}
+
+/// Returns values of the records matching the predicate
+fn matching_values<F>(fqdn: &str, records: &[Record], matches: &F) -> Vec<RecordMatch>
+where
+    F: Fn(&str, &Record, &str) -> bool,
+{
+    records
+        .iter()
+        .flat_map(|record| {
+            record
+                .rrset_values
+                .iter()
+                .filter(|value| matches(fqdn, record, value))
+                .map(|value| RecordMatch {
+                    fqdn: fqdn.to_owned(),
+                    rrset_name: record.rrset_name.to_owned(),
+                    rrset_type: record.rrset_type.to_owned(),
+                    rrset_value: value.to_owned(),
+                    rrset_ttl: record.rrset_ttl,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::matching_values;
+    use crate::{records::Record, Api};
+
+    #[test]
+    fn matching_record_values() {
+        let records = vec![
+            Record {
+                rrset_name: "www".to_owned(),
+                rrset_type: "A".to_owned(),
+                rrset_values: vec!["192.0.2.1".to_owned(), "192.0.2.2".to_owned()],
+                rrset_ttl: Some(300),
+            },
+            Record {
+                rrset_name: "@".to_owned(),
+                rrset_type: "MX".to_owned(),
+                rrset_values: vec!["10 mail.example.org.".to_owned()],
+                rrset_ttl: None,
+            },
+        ];
+
+        let matches = matching_values("example.org", &records, &|_, _, value: &str| {
+            value.ends_with(".2") || value.contains("mail")
+        });
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].rrset_name, "www");
+        assert_eq!(matches[0].rrset_value, "192.0.2.2");
+        assert_eq!(matches[1].fqdn, "example.org");
+        assert_eq!(matches[1].rrset_type, "MX");
+    }
+
+    #[tokio::test]
+    async fn search_records_empty() {
+        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
+
+        let api = Api::build(crate::Endpoint::Sandbox, &pat);
+
+        assert!(api.is_ok());
+
+        let api = api.unwrap();
+
+        let res = api.search_records(4, |_, _, _| true).await;
+
+        assert!(res.is_ok());
+
+        assert!(res.unwrap().is_empty());
+    }
+}
@@ -1,8 +1,11 @@
 //! Types for working with RESTful Api.
 
-use reqwest::{header, Client};
-use serde::de::DeserializeOwned;
-use std::error::Error;
+use reqwest::{header, Client, RequestBuilder};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    error::Error,
+    sync::{Mutex, PoisonError},
+};
 
 /// The engine ables to auth and query Gandi Api.
 pub(crate) struct Engine {
@@ -10,6 +13,21 @@ pub(crate) struct Engine {
     client: Client,
     /// The prod, sandbox or custom endpoint of the Gandi Api.
     endpoint: String,
+    /// The organization to act on behalf of.
+    sharing_id: Option<String>,
+    /// Requests modifying resources are only recorded in dry run mode.
+    dry_run: Option<Mutex<Vec<PlannedRequest>>>,
+}
+
+/// A request that would have been sent to Gandi Api in dry run mode.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PlannedRequest {
+    /// HTTP method (POST, PUT or DELETE)
+    pub method: String,
+    /// Full url, including the query
+    pub url: String,
+    /// JSON body
+    pub body: Option<String>,
 }
 
 /// Used to select the endpoint required.
@@ -58,16 +76,45 @@ impl Engine {
                 Endpoint::Sandbox => "https://api.sandbox.gandi.net/v5".to_owned(),
                 Endpoint::Custom(endpoint) => endpoint,
             },
+            sharing_id: None,
+            dry_run: None,
         })
     }
 
+    /// Record requests modifying resources instead of sending them.
+    pub(crate) fn set_dry_run(&mut self) {
+        self.dry_run = Some(Mutex::new(vec![]));
+    }
+
+    /// Returns requests recorded in dry run mode.
+    pub(crate) fn planned_requests(&self) -> Vec<PlannedRequest> {
+        match &self.dry_run {
+            Some(journal) => journal
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+            None => vec![],
+        }
+    }
+
+    /// Set the organization used by every query (`sharing_id` query parameter).
+    pub(crate) fn set_sharing_id(&mut self, sharing_id: &str) {
+        self.sharing_id = Some(sharing_id.to_owned());
+    }
+
+    fn with_sharing_id(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.sharing_id {
+            Some(sharing_id) => request.query(&[("sharing_id", sharing_id)]),
+            None => request,
+        }
+    }
+
     pub(crate) async fn get<T>(&self, url: &str) -> Result<T, Box<dyn Error>>
     where
         T: DeserializeOwned,
     {
         let response = self
-            .client
-            .get(format!("{}{}", self.endpoint, url))
+            .with_sharing_id(self.client.get(format!("{}{}", self.endpoint, url)))
             .send()
             .await?;
 
@@ -75,41 +122,49 @@ impl Engine {
     }
 
     pub(crate) async fn post(&self, url: &str, body: String) -> Result<(), reqwest::Error> {
-        let response = self
-            .client
-            .post(format!("{}{}", self.endpoint, url))
-            .header("content-type", "application/json")
-            .body(body)
-            .send()
-            .await?;
-
-        response.error_for_status()?;
-
-        Ok(())
+        self.send(
+            self.with_sharing_id(self.client.post(format!("{}{}", self.endpoint, url)))
+                .header("content-type", "application/json")
+                .body(body),
+        )
+        .await
     }
 
     pub(crate) async fn put(&self, url: &str, body: String) -> Result<(), reqwest::Error> {
-        let response = self
-            .client
-            .put(format!("{}{}", self.endpoint, url))
-            .header("content-type", "application/json")
-            .body(body)
-            .send()
-            .await?;
-
-        response.error_for_status()?;
-
-        Ok(())
+        self.send(
+            self.with_sharing_id(self.client.put(format!("{}{}", self.endpoint, url)))
+                .header("content-type", "application/json")
+                .body(body),
+        )
+        .await
     }
 
     pub(crate) async fn delete(&self, url: &str) -> Result<(), reqwest::Error> {
-        let response = self
-            .client
-            .delete(format!("{}{}", self.endpoint, url))
-            .send()
-            .await?;
+        self.send(self.with_sharing_id(self.client.delete(format!("{}{}", self.endpoint, url))))
+            .await
+    }
+
+    /// Send a request modifying resources or record it in dry run mode.
+    async fn send(&self, request: RequestBuilder) -> Result<(), reqwest::Error> {
+        if let Some(journal) = &self.dry_run {
+            let request = request.build()?;
+
+            journal
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(PlannedRequest {
+                    method: request.method().to_string(),
+                    url: request.url().to_string(),
+                    body: request
+                        .body()
+                        .and_then(|body| body.as_bytes())
+                        .map(|body| String::from_utf8_lossy(body).into_owned()),
+                });
+
+            return Ok(());
+        }
 
-        response.error_for_status()?;
+        request.send().await?.error_for_status()?;
 
         Ok(())
     }
@@ -155,6 +210,60 @@ mod tests {
         assert_eq!(engine.endpoint, "https://api.local");
     }
 
+    #[test]
+    fn sharing_id_query() {
+        let mut engine = Engine::build(Endpoint::Prod, "token").unwrap();
+
+        let request = engine
+            .with_sharing_id(
+                engine
+                    .client
+                    .get("https://api.gandi.net/v5/livedns/domains"),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(request.url().query(), None);
+
+        engine.set_sharing_id("organization-id");
+
+        let request = engine
+            .with_sharing_id(
+                engine
+                    .client
+                    .get("https://api.gandi.net/v5/livedns/domains"),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(request.url().query(), Some("sharing_id=organization-id"));
+    }
+
+    #[tokio::test]
+    async fn dry_run_records_requests() {
+        let mut engine =
+            Engine::build(Endpoint::Custom("http://127.0.0.1:9".to_owned()), "token").unwrap();
+        engine.set_dry_run();
+        engine.set_sharing_id("organization-id");
+
+        assert!(engine.put("/records/www/A", "{}".to_owned()).await.is_ok());
+        assert!(engine.delete("/records/www/A").await.is_ok());
+
+        assert_eq!(
+            engine.planned_requests(),
+            vec![
+                PlannedRequest {
+                    method: "PUT".to_owned(),
+                    url: "http://127.0.0.1:9/records/www/A?sharing_id=organization-id".to_owned(),
+                    body: Some("{}".to_owned()),
+                },
+                PlannedRequest {
+                    method: "DELETE".to_owned(),
+                    url: "http://127.0.0.1:9/records/www/A?sharing_id=organization-id".to_owned(),
+                    body: None,
+                },
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn check_bearer_header() {
         let engine =
@@ -23,8 +23,13 @@
 
 mod api;
 mod engine;
+mod token;
 
 pub use api::domains;
 pub use api::records;
+pub use api::search;
 pub use api::Api;
 pub use engine::Endpoint;
+pub use engine::PlannedRequest;
+pub use token::Token;
+pub use token::TokenSource;
@@ -0,0 +1,216 @@
+//! Types for working with personal access tokens.
+
+use serde::{Deserialize, Serialize};
+use std::{env, error::Error, fmt, fs, path::PathBuf, process::Command};
+
+/// A [personal access token](https://docs.gandi.net/en/managing_an_organization/organizations/personal_access_token.html#personal-access-tokens)
+///
+/// The token is redacted from `Debug` output.
+///
+/// # Examples
+///
+/// ```no_run
+/// let token = Token::new("secret");
+///
+/// let api = Api::build(Endpoint::Prod, token.expose())?;
+/// ```
+#[derive(Clone, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct Token(String);
+
+impl Token {
+    /// Returns a new [`Token`]
+    pub fn new(token: impl Into<String>) -> Self {
+        Token(token.into())
+    }
+
+    /// Returns the token in clear
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(***)")
+    }
+}
+
+/// Where to find a personal access token
+///
+/// # Examples
+///
+/// ```no_run
+/// let token = TokenSource::Env("GANDI_V5_PAT".to_owned()).token()?;
+/// let token = TokenSource::File("/run/credentials/gandi.token".into()).token()?;
+/// let token = TokenSource::Command("pass show gandi".to_owned()).token()?;
+/// let token = TokenSource::Keyring { service: "gandi".to_owned(), user: "me".to_owned() }.token()?;
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenSource {
+    /// Name of an environment variable
+    Env(String),
+    /// Path of a file holding the token
+    ///
+    /// A relative path is resolved from `$CREDENTIALS_DIRECTORY` when set (systemd credentials).
+    File(PathBuf),
+    /// Shell command printing the token on stdout (eg: `pass show gandi`)
+    Command(String),
+    /// Entry of the freedesktop Secret Service or the OS keyring (requires the `keyring` feature)
+    Keyring {
+        /// Service of the entry
+        service: String,
+        /// User of the entry
+        user: String,
+    },
+}
+
+impl TokenSource {
+    /// Returns the token read from the source
+    ///
+    /// Surrounding whitespaces are removed and an empty token is an error.
+    pub fn token(&self) -> Result<Token, Box<dyn Error>> {
+        let token = match self {
+            TokenSource::Env(name) => {
+                env::var(name).map_err(|e| format!("token: {} ({})", e, name))?
+            }
+            TokenSource::File(path) => {
+                let path = match env::var_os("CREDENTIALS_DIRECTORY") {
+                    Some(credentials_directory) if path.is_relative() => {
+                        PathBuf::from(credentials_directory).join(path)
+                    }
+                    _ => path.to_owned(),
+                };
+
+                fs::read_to_string(&path)
+                    .map_err(|e| format!("token: {} ({})", e, path.display()))?
+            }
+            TokenSource::Command(command) => {
+                let output = shell(command)
+                    .output()
+                    .map_err(|e| format!("token: {} ({})", e, command))?;
+
+                if !output.status.success() {
+                    return Err(format!("token: {} ({})", output.status, command).into());
+                }
+
+                String::from_utf8(output.stdout)?
+            }
+            TokenSource::Keyring { service, user } => keyring_password(service, user)?,
+        };
+
+        let token = token.trim();
+
+        if token.is_empty() {
+            return Err(format!("token: empty token ({})", self).into());
+        }
+
+        Ok(Token::new(token))
+    }
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::Env(name) => write!(f, "env {}", name),
+            TokenSource::File(path) => write!(f, "file {}", path.display()),
+            TokenSource::Command(command) => write!(f, "command {}", command),
+            TokenSource::Keyring { service, user } => write!(f, "keyring {}/{}", service, user),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_password(service: &str, user: &str) -> Result<String, Box<dyn Error>> {
+    keyring::Entry::new(service, user)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| format!("token: {} (keyring {}/{})", e, service, user).into())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_password(service: &str, user: &str) -> Result<String, Box<dyn Error>> {
+    Err(format!(
+        "token: keyring support is not enabled (keyring {}/{})",
+        service, user
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_is_redacted() {
+        let token = Token::new("secret");
+
+        assert_eq!(format!("{:?}", token), "Token(***)");
+        assert_eq!(token.expose(), "secret");
+    }
+
+    #[test]
+    fn token_from_env() {
+        env::set_var("GANDI_V5_LIVEDNS_API_TOKEN_TEST", " secret\n");
+
+        let token = TokenSource::Env("GANDI_V5_LIVEDNS_API_TOKEN_TEST".to_owned()).token();
+        assert_eq!(token.unwrap().expose(), "secret");
+
+        let token = TokenSource::Env("GANDI_V5_LIVEDNS_API_TOKEN_UNSET".to_owned()).token();
+        assert_eq!(
+            token.unwrap_err().to_string(),
+            "token: environment variable not found (GANDI_V5_LIVEDNS_API_TOKEN_UNSET)"
+        );
+    }
+
+    #[test]
+    fn token_from_file() {
+        let directory = env::temp_dir().join(format!("gandi-token-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("gandi.token"), "secret\n").unwrap();
+
+        let token = TokenSource::File(directory.join("gandi.token")).token();
+        assert_eq!(token.unwrap().expose(), "secret");
+
+        env::set_var("CREDENTIALS_DIRECTORY", &directory);
+        let token = TokenSource::File("gandi.token".into()).token();
+        env::remove_var("CREDENTIALS_DIRECTORY");
+        assert_eq!(token.unwrap().expose(), "secret");
+
+        fs::write(directory.join("gandi.token"), "\n").unwrap();
+        let token = TokenSource::File(directory.join("gandi.token")).token();
+        assert!(token
+            .unwrap_err()
+            .to_string()
+            .starts_with("token: empty token (file "));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_from_command() {
+        let token = TokenSource::Command("echo secret".to_owned()).token();
+        assert_eq!(token.unwrap().expose(), "secret");
+
+        let token = TokenSource::Command("exit 3".to_owned()).token();
+        assert_eq!(
+            token.unwrap_err().to_string(),
+            "token: exit status: 3 (exit 3)"
+        );
+    }
+}
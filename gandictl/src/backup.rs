@@ -0,0 +1,296 @@
+use futures::{stream, StreamExt, TryStreamExt};
+use gandi_v5_livedns_api::records::Record;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, error::Error, fs, path::Path, time::SystemTime};
+
+use crate::{
+    changes::{apply_all, diff, Change},
+    output::{render, Output},
+    session::Session,
+};
+
+const MANIFEST: &str = "manifest.yaml";
+
+/// Content of a backup directory
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct Manifest {
+    /// Date of the backup (RFC 3339)
+    created: String,
+    /// Domains saved
+    domains: Vec<BackupDomain>,
+    /// sha256 of each file of the backup
+    checksums: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct BackupDomain {
+    fqdn: String,
+    /// Number of records
+    records: usize,
+}
+
+/// Save records of every domain in a directory (`{fqdn}.yaml` and `{fqdn}.zone`) with a manifest
+pub(crate) async fn backup(
+    session: &Session,
+    directory: &Path,
+    concurrency: usize,
+) -> Result<(), Box<dyn Error>> {
+    let domains = session.api.domains().await?;
+
+    let mut results: Vec<(String, Result<Vec<Record>, String>)> = stream::iter(domains)
+        .map(|domain| async move {
+            let records = session
+                .api
+                .records(&domain.fqdn)
+                .await
+                .map_err(|e| e.to_string());
+
+            (domain.fqdn, records)
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    results.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    fs::create_dir_all(directory)?;
+
+    let mut manifest = Manifest {
+        created: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        ..Default::default()
+    };
+    let mut errors = vec![];
+
+    for (fqdn, records) in results {
+        let records = match records {
+            Ok(records) => records,
+            Err(e) => {
+                errors.push(format!("backup: {} ({})", e, fqdn));
+                continue;
+            }
+        };
+
+        let files = [
+            (format!("{}.yaml", fqdn), serde_yaml::to_string(&records)?),
+            (format!("{}.zone", fqdn), zone(&fqdn, &records)?),
+        ];
+
+        for (file, content) in files {
+            fs::write(directory.join(&file), &content)?;
+            manifest.checksums.insert(file, sha256(content.as_bytes()));
+        }
+
+        manifest.domains.push(BackupDomain {
+            fqdn,
+            records: records.len(),
+        });
+    }
+
+    fs::write(directory.join(MANIFEST), serde_yaml::to_string(&manifest)?)?;
+
+    println!(
+        "Backup of {} domains in {} !",
+        manifest.domains.len(),
+        directory.display()
+    );
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("\n").into()),
+    }
+}
+
+/// Replay records saved in a directory for some domains (all by default)
+///
+/// Changes are printed before being applied, records missing from the backup are deleted only with `prune`.
+pub(crate) async fn restore(
+    session: &Session,
+    directory: &Path,
+    fqdns: Vec<String>,
+    prune: bool,
+    concurrency: usize,
+) -> Result<(), Box<dyn Error>> {
+    let manifest: Manifest = serde_yaml::from_str(
+        &fs::read_to_string(directory.join(MANIFEST))
+            .map_err(|e| format!("restore: {} ({})", e, directory.join(MANIFEST).display()))?,
+    )?;
+
+    let fqdns = match fqdns.is_empty() {
+        true => manifest
+            .domains
+            .iter()
+            .map(|domain| domain.fqdn.to_owned())
+            .collect(),
+        false => fqdns
+            .into_iter()
+            .map(|fqdn| session.fqdn(fqdn))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    let mut targets = vec![];
+
+    for fqdn in fqdns {
+        if !manifest.domains.iter().any(|domain| domain.fqdn == fqdn) {
+            return Err(format!("restore: {} isn't in the backup", fqdn).into());
+        }
+
+        targets.push((fqdn.to_owned(), manifest.records(directory, &fqdn)?));
+    }
+
+    let plans: Vec<(String, Vec<Change>)> = stream::iter(targets)
+        .map(|(fqdn, target)| async move {
+            let current = session.api.records(&fqdn).await?;
+
+            Ok::<_, Box<dyn Error>>((fqdn, diff(&current, &target, prune)))
+        })
+        .buffered(concurrency)
+        .try_collect()
+        .await?;
+
+    let plans: Vec<_> = plans
+        .into_iter()
+        .filter(|(_, changes)| !changes.is_empty())
+        .collect();
+
+    if plans.is_empty() {
+        println!("Nothing to restore !");
+        return Ok(());
+    }
+
+    let mut count = 0;
+
+    for (fqdn, changes) in &plans {
+        println!("{}:", fqdn);
+
+        for change in changes {
+            let record = change.record();
+            session.protect(fqdn, &record.rrset_name, &record.rrset_type)?;
+
+            print!("{}", change);
+            count += 1;
+        }
+    }
+
+    if !session.yes && !session.dry_run {
+        session.ask(
+            &format!(
+                "Restore {} changes on {} domains? [y/N] ",
+                count,
+                plans.len()
+            ),
+            "Restore",
+        )?;
+    }
+
+    let applied = apply_all(session, &plans, concurrency).await;
+
+    if applied.changes > 0 {
+        session.notify(&format!(
+            "Restored {} changes on {} domains !",
+            applied.changes, applied.domains
+        ));
+    }
+
+    match applied.errors.is_empty() {
+        true => Ok(()),
+        false => Err(applied.errors.join("\n").into()),
+    }
+}
+
+impl Manifest {
+    /// Read the records of a domain and verify the checksum of the file
+    fn records(&self, directory: &Path, fqdn: &str) -> Result<Vec<Record>, Box<dyn Error>> {
+        let file = format!("{}.yaml", fqdn);
+        let content = fs::read_to_string(directory.join(&file))
+            .map_err(|e| format!("restore: {} ({})", e, file))?;
+
+        if self.checksums.get(&file) != Some(&sha256(content.as_bytes())) {
+            return Err(format!("restore: checksum mismatch ({})", file).into());
+        }
+
+        Ok(serde_yaml::from_str(&content)?)
+    }
+}
+
+/// Zone file of the records with the origin of the domain
+fn zone(fqdn: &str, records: &Vec<Record>) -> Result<String, Box<dyn Error>> {
+    Ok(format!(
+        "$ORIGIN {}.\n{}\n",
+        fqdn,
+        render(records, &Output::Zone)?.trim_end()
+    ))
+}
+
+fn sha256(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn checksum() {
+        assert_eq!(
+            sha256(b"gandi"),
+            "7643d02073069675c050f32de5318205116497649d63d40f7a78080cc31606c5"
+        );
+    }
+
+    #[test]
+    fn manifest_records() {
+        let directory = env::temp_dir().join(format!("gandictl-backup-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let records = vec![Record {
+            rrset_name: "www".to_owned(),
+            rrset_type: "A".to_owned(),
+            rrset_values: vec!["10.0.0.1".to_owned()],
+            rrset_ttl: Some(300),
+        }];
+        let content = serde_yaml::to_string(&records).unwrap();
+        fs::write(directory.join("example.org.yaml"), &content).unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest
+            .checksums
+            .insert("example.org.yaml".to_owned(), sha256(content.as_bytes()));
+
+        assert_eq!(
+            manifest.records(&directory, "example.org").unwrap(),
+            records
+        );
+
+        fs::write(directory.join("example.org.yaml"), "[]").unwrap();
+
+        assert_eq!(
+            manifest
+                .records(&directory, "example.org")
+                .unwrap_err()
+                .to_string(),
+            "restore: checksum mismatch (example.org.yaml)"
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn zone_file() {
+        let records = vec![Record {
+            rrset_name: "www".to_owned(),
+            rrset_type: "A".to_owned(),
+            rrset_values: vec!["10.0.0.1".to_owned()],
+            rrset_ttl: Some(300),
+        }];
+
+        assert_eq!(
+            zone("example.org", &records).unwrap(),
+            "$ORIGIN example.org.\nwww\t300\tIN\tA\t10.0.0.1\n"
+        );
+    }
+}
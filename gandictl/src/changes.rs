@@ -0,0 +1,309 @@
+use futures::{stream, StreamExt};
+use gandi_v5_livedns_api::records::{Record, UpsertRecord};
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use crate::session::Session;
+
+/// A modification of a record to reach a target state
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Change {
+    Create(Record),
+    Update { before: Record, after: Record },
+    Delete(Record),
+}
+
+impl Change {
+    /// The record targeted by the change
+    pub(crate) fn record(&self) -> &Record {
+        match self {
+            Change::Create(record) | Change::Delete(record) => record,
+            Change::Update { after, .. } => after,
+        }
+    }
+
+    /// The change undoing this change
+    pub(crate) fn revert(&self) -> Change {
+        match self {
+            Change::Create(record) => Change::Delete(record.to_owned()),
+            Change::Update { before, after } => Change::Update {
+                before: after.to_owned(),
+                after: before.to_owned(),
+            },
+            Change::Delete(record) => Change::Create(record.to_owned()),
+        }
+    }
+
+    /// Send the request applying the change
+    pub(crate) async fn apply(&self, session: &Session, fqdn: &str) -> Result<(), Box<dyn Error>> {
+        let record = self.record();
+        let upsert = UpsertRecord {
+            rrset_values: record.rrset_values.to_owned(),
+            rrset_ttl: record.rrset_ttl,
+        };
+
+        match self {
+            Change::Create(_) => {
+                session
+                    .api
+                    .create_record_by_name_and_type(
+                        fqdn,
+                        &record.rrset_name,
+                        &record.rrset_type,
+                        &upsert,
+                    )
+                    .await?
+            }
+            Change::Update { .. } => {
+                session
+                    .api
+                    .upsert_record_by_name_and_type(
+                        fqdn,
+                        &record.rrset_name,
+                        &record.rrset_type,
+                        &upsert,
+                    )
+                    .await?
+            }
+            Change::Delete(_) => {
+                session
+                    .api
+                    .delete_record_by_name_and_type(fqdn, &record.rrset_name, &record.rrset_type)
+                    .await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = |f: &mut fmt::Formatter<'_>, sign: char, record: &Record| {
+            writeln!(
+                f,
+                "{} {} {} {} {}",
+                sign,
+                record.rrset_name,
+                record.rrset_type,
+                record
+                    .rrset_ttl
+                    .map(|ttl| ttl.to_string())
+                    .unwrap_or("default".to_owned()),
+                record.rrset_values.join(",")
+            )
+        };
+
+        match self {
+            Change::Create(record) => line(f, '+', record),
+            Change::Update { before, after } => {
+                line(f, '-', before)?;
+                line(f, '+', after)
+            }
+            Change::Delete(record) => line(f, '-', record),
+        }
+    }
+}
+
+/// Changes applied successfully by [`apply_all`]
+pub(crate) struct Applied {
+    pub(crate) changes: usize,
+    /// Domains with at least one change applied
+    pub(crate) domains: usize,
+    pub(crate) errors: Vec<String>,
+}
+
+/// Apply the changes of the domains, domains processed concurrently
+///
+/// The changes of a domain are applied in order and stopped at the first error.
+pub(crate) async fn apply_all(
+    session: &Session,
+    plans: &[(String, Vec<Change>)],
+    concurrency: usize,
+) -> Applied {
+    let results: Vec<(usize, Option<String>)> = stream::iter(plans)
+        .map(|(fqdn, changes)| async move {
+            let mut applied = 0;
+
+            for change in changes {
+                if let Err(e) = change.apply(session, fqdn).await {
+                    return (applied, Some(e.to_string()));
+                }
+
+                applied += 1;
+            }
+
+            (applied, None)
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    Applied {
+        changes: results.iter().map(|(applied, _)| applied).sum(),
+        domains: results.iter().filter(|(applied, _)| *applied > 0).count(),
+        errors: results.into_iter().filter_map(|(_, e)| e).collect(),
+    }
+}
+
+/// Returns the changes to turn the current records into the target records
+///
+/// Records missing from the target are deleted only with `prune`.
+pub(crate) fn diff(current: &[Record], target: &[Record], prune: bool) -> Vec<Change> {
+    let key = |record: &Record| (record.rrset_name.to_owned(), record.rrset_type.to_owned());
+
+    let current: BTreeMap<_, _> = current.iter().map(|record| (key(record), record)).collect();
+    let target: BTreeMap<_, _> = target.iter().map(|record| (key(record), record)).collect();
+
+    let mut changes = vec![];
+
+    for (key, after) in &target {
+        match current.get(key) {
+            None => changes.push(Change::Create((*after).to_owned())),
+            Some(before) if !same(before, after) => changes.push(Change::Update {
+                before: (*before).to_owned(),
+                after: (*after).to_owned(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    if prune {
+        for (key, before) in &current {
+            if !target.contains_key(key) {
+                changes.push(Change::Delete((*before).to_owned()));
+            }
+        }
+    }
+
+    changes
+}
+
+/// Returns true if both records have the same values (in any order) and ttl
+fn same(a: &Record, b: &Record) -> bool {
+    let mut a_values = a.rrset_values.to_owned();
+    let mut b_values = b.rrset_values.to_owned();
+    a_values.sort();
+    b_values.sort();
+
+    a_values == b_values && a.rrset_ttl == b.rrset_ttl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+    use crate::output::Output;
+    use gandi_v5_livedns_api::{Api, Endpoint};
+
+    fn session(endpoint: &str, dry_run: bool) -> Session {
+        let mut api = Api::build(Endpoint::Custom(endpoint.to_owned()), "token").unwrap();
+
+        if dry_run {
+            api = api.with_dry_run();
+        }
+
+        Session {
+            api,
+            endpoint: endpoint.to_owned(),
+            output: Output::Yaml,
+            domain: None,
+            dry_run,
+            yes: true,
+            force: false,
+            protected: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_all_counts_applied() {
+        let plans = vec![
+            (
+                "example.org".to_owned(),
+                vec![
+                    Change::Create(record("www", "A", &["10.0.0.1"])),
+                    Change::Delete(record("old", "A", &["10.0.0.2"])),
+                ],
+            ),
+            (
+                "example.net".to_owned(),
+                vec![Change::Create(record("www", "A", &["10.0.0.1"]))],
+            ),
+        ];
+
+        let applied = apply_all(&session("http://127.0.0.1:9", true), &plans, 2).await;
+        assert_eq!((applied.changes, applied.domains), (3, 2));
+        assert!(applied.errors.is_empty());
+
+        // nothing listens on the discard port
+        let applied = apply_all(&session("http://127.0.0.1:9", false), &plans, 2).await;
+        assert_eq!((applied.changes, applied.domains), (0, 0));
+        assert_eq!(applied.errors.len(), 2);
+    }
+
+    #[test]
+    fn diff_records() {
+        let current = vec![
+            record("www", "A", &["10.0.0.1", "10.0.0.2"]),
+            record("mail", "A", &["10.0.0.3"]),
+            record("old", "CNAME", &["www"]),
+        ];
+        let target = vec![
+            record("www", "A", &["10.0.0.2", "10.0.0.1"]),
+            record("mail", "A", &["10.0.0.4"]),
+            record("new", "A", &["10.0.0.5"]),
+        ];
+
+        let changes = diff(&current, &target, false);
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Update {
+                    before: record("mail", "A", &["10.0.0.3"]),
+                    after: record("mail", "A", &["10.0.0.4"]),
+                },
+                Change::Create(record("new", "A", &["10.0.0.5"])),
+            ]
+        );
+
+        let changes = diff(&current, &target, true);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[2], Change::Delete(record("old", "CNAME", &["www"])));
+    }
+
+    #[test]
+    fn revert_change() {
+        let www = record("www", "A", &["10.0.0.1"]);
+        let mail = record("mail", "A", &["10.0.0.3"]);
+
+        assert_eq!(
+            Change::Create(www.to_owned()).revert(),
+            Change::Delete(www.to_owned())
+        );
+        assert_eq!(
+            Change::Update {
+                before: www.to_owned(),
+                after: mail.to_owned()
+            }
+            .revert(),
+            Change::Update {
+                before: mail,
+                after: www
+            }
+        );
+    }
+
+    #[test]
+    fn display_change() {
+        let change = Change::Update {
+            before: record("mail", "A", &["10.0.0.3"]),
+            after: record("mail", "A", &["10.0.0.4"]),
+        };
+
+        assert_eq!(
+            change.to_string(),
+            "- mail A 300 10.0.0.3\n+ mail A 300 10.0.0.4\n"
+        );
+    }
+}
@@ -1,6 +1,16 @@
-use std::ops::RangeInclusive;
+use std::{ops::RangeInclusive, path::PathBuf, time::Duration};
 
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand};
+use clap_complete::{engine::ArgValueCompleter, CompleteEnv, Shell};
+
+use crate::{
+    completion::{domains, rrset_names, COMPLETE_VAR},
+    config::endpoint_is_valid,
+    copy::Mode as CopyMode,
+    ddns::Discovery,
+    lint::Severity,
+    output::Output,
+};
 
 /// Control Gandi services
 #[derive(Parser)]
@@ -8,15 +18,46 @@ use clap::{Parser, Subcommand};
 pub(crate) struct Cli {
     #[command(subcommand)]
     pub(crate) command: ApiCommands,
+    #[command(flatten)]
+    pub(crate) global: GlobalOptions,
+}
+
+#[derive(Args)]
+pub(crate) struct GlobalOptions {
     /// Gandi Sandbox Api
-    #[arg(short)]
+    #[arg(short, conflicts_with = "endpoint")]
     pub(crate) sandbox: bool,
+    /// Gandi Api endpoint: prod, sandbox or a custom url (overrides the context)
+    #[arg(long, global = true, value_parser = endpoint_is_valid)]
+    pub(crate) endpoint: Option<String>,
+    /// Context to use instead of the current context
+    #[arg(long, global = true)]
+    pub(crate) context: Option<String>,
+    /// Output format: yaml, json, table, wide, name, zone or template='{{rrset_name}} {{rrset_values}}'
+    #[arg(short, long, global = true, default_value = "yaml")]
+    pub(crate) output: Output,
+    /// Print requests that would be sent without modifying any resource
+    #[arg(long, global = true)]
+    pub(crate) dry_run: bool,
+    /// Don't ask for confirmation before overwriting or deleting records
+    #[arg(short, long, global = true)]
+    pub(crate) yes: bool,
+    /// Allow to modify protected records
+    #[arg(long, global = true)]
+    pub(crate) force: bool,
 }
 
 impl Cli {
     pub(crate) fn init() -> Self {
         Cli::parse()
     }
+
+    /// Answer the shell and exit when completions are requested
+    pub(crate) fn complete() {
+        CompleteEnv::with_factory(Cli::command)
+            .var(COMPLETE_VAR)
+            .complete();
+    }
 }
 
 #[derive(Subcommand)]
@@ -26,6 +67,117 @@ pub(crate) enum ApiCommands {
         #[command(subcommand)]
         command: LiveDnsCommands,
     },
+    /// Update records with the public IP addresses (Dynamic DNS)
+    Ddns(DdnsArgs),
+    /// Modify gandictl config file (contexts)
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Print the shell completion script (eg: source <(gandictl completion bash))
+    Completion {
+        /// Shell to complete
+        shell: Shell,
+    },
+    /// Generate man pages
+    Man {
+        /// Directory of the man pages
+        #[arg(default_value = ".")]
+        directory: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub(crate) struct DdnsArgs {
+    /// Domain name ('.' for the default domain of the context)
+    #[arg(add = ArgValueCompleter::new(domains))]
+    pub(crate) fqdn: String,
+    /// Name of the record
+    #[arg(add = ArgValueCompleter::new(rrset_names))]
+    pub(crate) rrset_name: String,
+    /// Types of the record to update: A (ipv4) and/or AAAA (ipv6) (comma delimiter)
+    #[arg(long, value_delimiter = ',', default_value = "A", value_parser = ["A", "AAAA"])]
+    pub(crate) rrset_types: Vec<String>,
+    /// The time in seconds that DNS resolvers should cache this record (300 to 2592000)
+    #[arg(long, value_parser = rrset_ttl_in_range)]
+    pub(crate) rrset_ttl: Option<u32>,
+    /// Methods to discover the public addresses, tried in order (comma delimiter)
+    #[arg(long, value_delimiter = ',', default_value = "http,stun")]
+    pub(crate) discovery: Vec<Discovery>,
+    /// HTTP services returning the public ipv4 (comma delimiter)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "https://ipv4.seeip.org/,https://api.ipify.org/"
+    )]
+    pub(crate) http_ipv4_url: Vec<String>,
+    /// HTTP services returning the public ipv6 (comma delimiter)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "https://ipv6.seeip.org/,https://api6.ipify.org/"
+    )]
+    pub(crate) http_ipv6_url: Vec<String>,
+    /// Network interface holding the public address (interface discovery; default: any)
+    #[arg(long)]
+    pub(crate) interface: Option<String>,
+    /// STUN servers host:port (comma delimiter)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "stun.l.google.com:19302,stun.cloudflare.com:3478"
+    )]
+    pub(crate) stun_server: Vec<String>,
+    /// Directory of the last known addresses (default: ~/.cache/gandictl/ddns)
+    #[arg(long)]
+    pub(crate) cache_dir: Option<PathBuf>,
+    /// Age after which the record is read again from Gandi Api, even if the address didn't change (eg: 30m, 1h)
+    #[arg(long, default_value = "1h", value_parser = interval)]
+    pub(crate) cache_max_age: Duration,
+    /// Keep running and update records periodically
+    #[arg(long)]
+    pub(crate) daemon: bool,
+    /// Interval between updates with --daemon (eg: 30s, 5m, 1h)
+    #[arg(long, default_value = "5m", value_parser = interval)]
+    pub(crate) interval: Duration,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum ConfigCommands {
+    /// Set the current context
+    UseContext {
+        /// Name of the context
+        name: String,
+    },
+    /// Display contexts
+    GetContexts {},
+    /// Create or update a context
+    #[command(group(ArgGroup::new("token").args(["token_env", "token_file", "token_command", "token_keyring"])))]
+    SetContext {
+        /// Name of the context
+        name: String,
+        /// Gandi Api endpoint: prod, sandbox or a custom url
+        #[arg(long, value_parser = endpoint_is_valid)]
+        endpoint: Option<String>,
+        /// Environment variable holding the personal access token
+        #[arg(long)]
+        token_env: Option<String>,
+        /// File holding the personal access token (relative to $CREDENTIALS_DIRECTORY if set)
+        #[arg(long)]
+        token_file: Option<PathBuf>,
+        /// Command printing the personal access token (eg: 'pass show gandi')
+        #[arg(long)]
+        token_command: Option<String>,
+        /// Secret Service / OS keyring entry holding the personal access token
+        #[arg(long, num_args = 2, value_names = ["SERVICE", "USER"])]
+        token_keyring: Option<Vec<String>>,
+        /// Organization to act on behalf of
+        #[arg(long)]
+        sharing_id: Option<String>,
+        /// Default domain used when the fqdn is '.'
+        #[arg(long)]
+        domain: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -45,11 +197,100 @@ pub(crate) enum LiveDnsCommands {
         #[command(subcommand)]
         command: LiveDnsCreateCommands,
     },
+    /// Add or remove values of one or many resources.
+    Patch {
+        #[command(subcommand)]
+        command: LiveDnsPatchCommands,
+    },
+    /// Rename one or many resources.
+    Rename {
+        #[command(subcommand)]
+        command: LiveDnsRenameCommands,
+    },
     /// Delete one or many resources.
     Delete {
         #[command(subcommand)]
         command: LiveDnsDeleteCommands,
     },
+    /// Set or restore the ttl of many records
+    Ttl {
+        #[command(subcommand)]
+        command: LiveDnsTtlCommands,
+    },
+    /// Report common misconfigurations of a zone
+    Lint {
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(default_value = ".", add = ArgValueCompleter::new(domains))]
+        fqdn: String,
+        /// Lint records of a yaml or json file (eg: from a backup) instead of the records of the domain
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Exit with an error when a finding is at least this severe
+        #[arg(long, default_value = "error")]
+        fail_on: Severity,
+    },
+    /// Find values of records across every domain
+    Search {
+        /// Regex matching values (case insensitive)
+        value: String,
+        /// Match a whole hostname or address of the values instead of a regex
+        #[arg(short, long)]
+        exact: bool,
+        /// Types of the records (comma delimiter)
+        #[arg(short = 't', long = "type", value_delimiter = ',')]
+        rrset_types: Vec<String>,
+        /// Name of the records (glob pattern, eg: 'www*')
+        #[arg(short = 'n', long = "name")]
+        rrset_name: Option<String>,
+        /// Number of domains processed at the same time
+        #[arg(long, default_value = "8", value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+    },
+    /// Copy records of a domain to other domains
+    Copy {
+        /// Source domain ('.' for the default domain of the context)
+        #[arg(long, add = ArgValueCompleter::new(domains))]
+        from: String,
+        /// Destination domains (comma delimiter)
+        #[arg(long, required = true, value_delimiter = ',', add = ArgValueCompleter::new(domains))]
+        to: Vec<String>,
+        /// Names of the records to copy (comma delimiter, glob patterns, default: all)
+        #[arg(long, value_delimiter = ',')]
+        names: Vec<String>,
+        /// Types of the records to copy (comma delimiter, default: all)
+        #[arg(long, value_delimiter = ',')]
+        types: Vec<String>,
+        /// merge: add values to existing records, replace: overwrite selected records and delete the others
+        #[arg(long, default_value = "merge")]
+        mode: CopyMode,
+        /// Number of domains processed at the same time
+        #[arg(long, default_value = "8", value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+    },
+    /// Save records of every domain in a directory (yaml and zone files)
+    Backup {
+        /// Directory of the backup
+        #[arg(long)]
+        dir: PathBuf,
+        /// Number of domains processed at the same time
+        #[arg(long, default_value = "8", value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+    },
+    /// Replay records of a backup
+    Restore {
+        /// Directory of the backup
+        #[arg(long)]
+        dir: PathBuf,
+        /// Domains to restore (default: all domains of the backup)
+        #[arg(add = ArgValueCompleter::new(domains))]
+        fqdns: Vec<String>,
+        /// Delete records missing from the backup
+        #[arg(long)]
+        prune: bool,
+        /// Number of domains processed at the same time
+        #[arg(long, default_value = "8", value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+    },
 }
 
 #[derive(Subcommand)]
@@ -58,22 +299,32 @@ pub(crate) enum LiveDnsGetCommands {
     Domains {},
     /// Show domain's properties
     Domain {
-        /// Domain name
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(default_value = ".", add = ArgValueCompleter::new(domains))]
         fqdn: String,
     },
     /// List records or named records associated with a domain
     Records {
-        /// Domain name
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(default_value = ".", add = ArgValueCompleter::new(domains))]
         fqdn: String,
         /// Name of the record
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCompleter::new(rrset_names))]
         rrset_name: Option<String>,
+        /// Poll the records and print added, removed or modified records until Ctrl+C
+        #[arg(short, long)]
+        watch: bool,
+        /// Interval between polls with --watch (eg: 30s, 5m)
+        #[arg(long, default_value = "30s", value_parser = interval, requires = "watch")]
+        interval: Duration,
     },
     /// Get a single record with its name and type
     Record {
-        /// Domain name
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
         fqdn: String,
         /// Name of the record
+        #[arg(add = ArgValueCompleter::new(rrset_names))]
         rrset_name: String,
         /// Type of the record
         rrset_type: String,
@@ -84,9 +335,11 @@ pub(crate) enum LiveDnsGetCommands {
 pub(crate) enum LiveDnsApplyCommands {
     /// Overwrites a single record with {rrset_name} and {rrset_type}
     Record {
-        /// Domain name
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
         fqdn: String,
         /// Name of the record
+        #[arg(add = ArgValueCompleter::new(rrset_names))]
         rrset_name: String,
         /// Type of the record
         rrset_type: String,
@@ -103,9 +356,11 @@ pub(crate) enum LiveDnsApplyCommands {
 pub(crate) enum LiveDnsCreateCommands {
     /// Create a new record for {rrset_name} and {rrset_type}
     Record {
-        /// Domain name
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
         fqdn: String,
         /// Name of the record
+        #[arg(add = ArgValueCompleter::new(rrset_names))]
         rrset_name: String,
         /// Type of the record
         rrset_type: String,
@@ -118,20 +373,106 @@ pub(crate) enum LiveDnsCreateCommands {
     },
 }
 
+#[derive(Subcommand)]
+pub(crate) enum LiveDnsPatchCommands {
+    /// Add or remove values of a single record with {rrset_name} and {rrset_type}
+    ///
+    /// The record is created if it doesn't exist and deleted when no value remains.
+    #[command(group(ArgGroup::new("patch").args(["add_value", "remove_value", "rrset_ttl"]).required(true).multiple(true)))]
+    Record {
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
+        fqdn: String,
+        /// Name of the record
+        #[arg(add = ArgValueCompleter::new(rrset_names))]
+        rrset_name: String,
+        /// Type of the record
+        rrset_type: String,
+        /// A value to add (repeatable)
+        #[arg(long)]
+        add_value: Vec<String>,
+        /// A value to remove (repeatable)
+        #[arg(long)]
+        remove_value: Vec<String>,
+        /// The time in seconds that DNS resolvers should cache this record (300 to 2592000)
+        #[arg(long, visible_alias = "ttl", value_parser = rrset_ttl_in_range)]
+        rrset_ttl: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum LiveDnsRenameCommands {
+    /// Move the records of {old_name} (all types by default) to {new_name}
+    ///
+    /// The created records are deleted again when the old ones can't be deleted.
+    Record {
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
+        fqdn: String,
+        /// Current name of the records
+        #[arg(add = ArgValueCompleter::new(rrset_names))]
+        old_name: String,
+        /// New name of the records
+        new_name: String,
+        /// Type of the record to move
+        #[arg(short = 't', long = "type")]
+        rrset_type: Option<String>,
+        /// Leave a CNAME to {new_name} behind
+        #[arg(long)]
+        cname: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub(crate) enum LiveDnsTtlCommands {
+    /// Set the ttl of the records which have another ttl, previous ttls are saved for `ttl restore`
+    Set {
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
+        fqdn: String,
+        /// The time in seconds that DNS resolvers should cache the records (300 to 2592000)
+        #[arg(value_parser = rrset_ttl_in_range)]
+        rrset_ttl: u32,
+        /// Name of the records (glob pattern, eg: 'www*')
+        #[arg(short = 'n', long = "name")]
+        rrset_name: Option<String>,
+        /// Types of the records (comma delimiter)
+        #[arg(short = 't', long = "type", value_delimiter = ',')]
+        rrset_types: Vec<String>,
+        /// Only records with a ttl above this ttl
+        #[arg(long)]
+        only_above: Option<u32>,
+        /// State file of the previous ttls (default: $XDG_STATE_HOME/gandictl/ttl/{fqdn}.yaml)
+        #[arg(long)]
+        state: Option<PathBuf>,
+    },
+    /// Reapply the ttls saved by the previous `ttl set`
+    Restore {
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
+        fqdn: String,
+        /// State file of the previous ttls (default: $XDG_STATE_HOME/gandictl/ttl/{fqdn}.yaml)
+        #[arg(long)]
+        state: Option<PathBuf>,
+    },
+}
+
 #[derive(Subcommand)]
 pub(crate) enum LiveDnsDeleteCommands {
     /// Delete a single record with {rrset_name} and {rrset_type}
     Record {
-        /// Domain name
+        /// Domain name ('.' for the default domain of the context)
+        #[arg(add = ArgValueCompleter::new(domains))]
         fqdn: String,
         /// Name of the record
+        #[arg(add = ArgValueCompleter::new(rrset_names))]
         rrset_name: String,
         /// Type of the record
         rrset_type: String,
     },
 }
 
-const RRSET_TTL_RANGE: RangeInclusive<usize> = 300..=2592000;
+pub(crate) const RRSET_TTL_RANGE: RangeInclusive<usize> = 300..=2592000;
 
 fn rrset_ttl_in_range(rrset_ttl: &str) -> Result<u32, String> {
     let rrset_ttl: usize = rrset_ttl
@@ -149,9 +490,20 @@ fn rrset_ttl_in_range(rrset_ttl: &str) -> Result<u32, String> {
     }
 }
 
+fn interval(interval: &str) -> Result<Duration, String> {
+    let interval = humantime::parse_duration(interval).map_err(|e| e.to_string())?;
+
+    if interval < Duration::from_secs(1) {
+        return Err("interval must be at least 1s".to_owned());
+    }
+
+    Ok(interval)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::rrset_ttl_in_range;
+    use super::{interval, rrset_ttl_in_range};
+    use std::time::Duration;
 
     #[test]
     fn rrset_ttl_in_range_ok() {
@@ -179,4 +531,15 @@ mod tests {
         assert!(res.is_err());
         assert_eq!(res.err().unwrap(), "'not_a_number isn't a ttl number'");
     }
+
+    #[test]
+    fn interval_parse() {
+        assert_eq!(interval("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(interval("1h 30s"), Ok(Duration::from_secs(3630)));
+        assert_eq!(
+            interval("100ms").unwrap_err(),
+            "interval must be at least 1s"
+        );
+        assert!(interval("soon").is_err());
+    }
 }
@@ -0,0 +1,303 @@
+use clap::CommandFactory;
+use clap_complete::{engine::CompletionCandidate, env::Shells, Shell};
+use std::{
+    env,
+    error::Error,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    cli::{Cli, GlobalOptions},
+    config::{cache_directory, endpoint_name, GandiCtlConfig},
+    output::Output,
+    session::{endpoint, Session},
+};
+
+/// Environment variable set by the shell to request completions
+pub(crate) const COMPLETE_VAR: &str = "COMPLETE";
+
+/// How long domains and rrset names are kept before querying Gandi Api again
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Print the script registering the completion of gandictl in the shell
+pub(crate) fn completion(shell: Shell) -> Result<(), Box<dyn Error>> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .ok_or(format!("completion: {} isn't supported", shell))?;
+
+    completer.write_registration(
+        COMPLETE_VAR,
+        "gandictl",
+        "gandictl",
+        "gandictl",
+        &mut io::stdout(),
+    )?;
+
+    Ok(())
+}
+
+/// Generate man pages of gandictl and its subcommands in a directory
+pub(crate) fn man(directory: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(directory)?;
+    clap_mangen::generate_to(Cli::command(), directory)?;
+
+    Ok(())
+}
+
+/// Complete domain names
+pub(crate) fn domains(current: &OsStr) -> Vec<CompletionCandidate> {
+    candidates(current, Lookup::Domains)
+}
+
+/// Complete rrset names of the domain on the command line
+pub(crate) fn rrset_names(current: &OsStr) -> Vec<CompletionCandidate> {
+    let words = words();
+
+    candidates(current, Lookup::RrsetNames(fqdn(&words)))
+}
+
+/// Resources queried to complete a value
+#[derive(Debug, PartialEq)]
+enum Lookup {
+    Domains,
+    RrsetNames(String),
+}
+
+impl Lookup {
+    /// File name in the cache of the context
+    fn key(&self) -> String {
+        match self {
+            Lookup::Domains => "domains".to_owned(),
+            Lookup::RrsetNames(fqdn) => format!("records_{}", fqdn),
+        }
+    }
+
+    async fn fetch(&self, session: &Session) -> Result<Vec<String>, Box<dyn Error>> {
+        match self {
+            Lookup::Domains => Ok(session
+                .api
+                .domains()
+                .await?
+                .into_iter()
+                .map(|domain| domain.fqdn)
+                .collect()),
+            Lookup::RrsetNames(fqdn) => {
+                let fqdn = session.fqdn(fqdn.to_owned())?;
+
+                let mut names: Vec<String> = session
+                    .api
+                    .records(&fqdn)
+                    .await?
+                    .into_iter()
+                    .map(|record| record.rrset_name)
+                    .collect();
+
+                names.sort();
+                names.dedup();
+
+                Ok(names)
+            }
+        }
+    }
+}
+
+/// Returns values starting with `current`, errors are silently ignored
+fn candidates(current: &OsStr, lookup: Lookup) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+
+    cached_lookup(&options(&words()), &lookup)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|value| value.starts_with(current.as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Returns values from the cache of the context and endpoint or query Gandi Api when the cache is expired
+fn cached_lookup(options: &GlobalOptions, lookup: &Lookup) -> Result<Vec<String>, Box<dyn Error>> {
+    let config = GandiCtlConfig::load(&GandiCtlConfig::path()?)?;
+    let name = options
+        .context
+        .as_deref()
+        .or(config.current_context.as_deref())
+        .unwrap_or("default");
+    let endpoint = endpoint(options, &config.context(options.context.as_deref())?)?;
+
+    // names of prod and sandbox aren't mixed
+    let cache = Cache {
+        directory: cache_directory()?
+            .join("completion")
+            .join(name)
+            .join(endpoint_name(&endpoint)),
+        ttl: CACHE_TTL,
+    };
+
+    if let Some(values) = cache.get(&lookup.key()) {
+        return Ok(values);
+    }
+
+    let session = Session::build(options)?;
+
+    // completers are called before the runtime of main is started
+    let values = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(lookup.fetch(&session))?;
+
+    cache.set(&lookup.key(), &values)?;
+
+    Ok(values)
+}
+
+/// Words of the command line being completed (after `--`)
+fn words() -> Vec<String> {
+    env::args().skip_while(|arg| arg != "--").skip(1).collect()
+}
+
+/// Global options provided on the command line being completed
+fn options(words: &[String]) -> GlobalOptions {
+    GlobalOptions {
+        sandbox: words.iter().any(|word| word == "-s"),
+        endpoint: option(words, "--endpoint"),
+        context: option(words, "--context"),
+        output: Output::Yaml,
+        dry_run: false,
+        yes: false,
+        force: false,
+    }
+}
+
+/// Returns the value of a long option (`--name value` or `--name=value`)
+fn option(words: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+
+    words.iter().enumerate().find_map(|(i, word)| {
+        if word == name {
+            words.get(i + 1).cloned()
+        } else {
+            word.strip_prefix(&prefix).map(str::to_owned)
+        }
+    })
+}
+
+/// Returns the fqdn following a record(s) or ddns subcommand ('.' by default)
+fn fqdn(words: &[String]) -> String {
+    let Some(i) = words
+        .iter()
+        .position(|word| matches!(word.as_str(), "record" | "records" | "ddns"))
+    else {
+        return ".".to_owned();
+    };
+
+    let mut words = words[i + 1..].iter();
+
+    while let Some(word) = words.next() {
+        match word.as_str() {
+            // options with a value
+            "-r" | "--rrset-name" | "--context" | "--endpoint" | "-o" | "--output" => {
+                words.next();
+            }
+            option if option.starts_with('-') => {}
+            fqdn => return fqdn.to_owned(),
+        }
+    }
+
+    ".".to_owned()
+}
+
+/// Values stored one per line in a file, expired after a ttl
+struct Cache {
+    directory: PathBuf,
+    ttl: Duration,
+}
+
+impl Cache {
+    fn get(&self, key: &str) -> Option<Vec<String>> {
+        let path = self.directory.join(key);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+
+        if SystemTime::now().duration_since(modified).ok()? > self.ttl {
+            return None;
+        }
+
+        let values = fs::read_to_string(path).ok()?;
+
+        Some(values.lines().map(str::to_owned).collect())
+    }
+
+    fn set(&self, key: &str, values: &[String]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.directory.join(key), values.join("\n"))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split(' ').map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn options_from_words() {
+        let options = options(&words(
+            "gandictl --context company live-dns get records --endpoint=sandbox ",
+        ));
+
+        assert_eq!(options.context.as_deref(), Some("company"));
+        assert_eq!(options.endpoint.as_deref(), Some("sandbox"));
+        assert!(!options.sandbox);
+    }
+
+    #[test]
+    fn fqdn_from_words() {
+        assert_eq!(
+            fqdn(&words("gandictl live-dns get record example.org w")),
+            "example.org"
+        );
+        assert_eq!(fqdn(&words("gandictl live-dns get records -r w")), ".");
+        assert_eq!(fqdn(&words("gandictl ddns . home")), ".");
+        assert_eq!(fqdn(&words("gandictl -s ddns example.net ")), "example.net");
+    }
+
+    #[test]
+    fn lookup_keys() {
+        assert_eq!(Lookup::Domains.key(), "domains");
+        assert_eq!(
+            Lookup::RrsetNames("example.org".to_owned()).key(),
+            "records_example.org"
+        );
+    }
+
+    #[test]
+    fn cache() {
+        let directory = env::temp_dir().join(format!("gandictl-completion-{}", std::process::id()));
+        let values = vec!["example.org".to_owned(), "example.net".to_owned()];
+
+        let cache = Cache {
+            directory: directory.to_owned(),
+            ttl: CACHE_TTL,
+        };
+
+        assert_eq!(cache.get("domains"), None);
+        cache.set("domains", &values).unwrap();
+        assert_eq!(cache.get("domains"), Some(values));
+
+        let expired = Cache {
+            directory: directory.to_owned(),
+            ttl: Duration::ZERO,
+        };
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(expired.get("domains"), None);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+use gandi_v5_livedns_api::{Endpoint, TokenSource};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::output::Resource;
+
+/// Environment variable used to read the personal access token by default
+pub(crate) const DEFAULT_TOKEN_ENV: &str = "GANDI_V5_PAT";
+
+/// gandictl configuration file (`~/.config/gandictl/config.yaml`)
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct GandiCtlConfig {
+    /// Context used when `--context` is not provided
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) current_context: Option<String>,
+    /// Named contexts
+    #[serde(default)]
+    pub(crate) contexts: BTreeMap<String, Context>,
+    /// Records that can't be modified without `--force`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) protected: Vec<ProtectedRecord>,
+}
+
+/// An account and endpoint to work with
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Context {
+    /// prod, sandbox or a custom url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) endpoint: Option<String>,
+    /// Where to find the personal access token (default: `GANDI_V5_PAT` environment variable)
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map"
+    )]
+    pub(crate) token: Option<TokenSource>,
+    /// Organization to act on behalf of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) sharing_id: Option<String>,
+    /// Domain used when the fqdn is '.'
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) domain: Option<String>,
+}
+
+/// Records that can't be modified without `--force`
+///
+/// Each field is a glob pattern (eg: `*._domainkey`), a missing field matches any value.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub(crate) struct ProtectedRecord {
+    /// Domain name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) fqdn: Option<String>,
+    /// Name of the record (eg: @)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    /// Type of the record (eg: MX)
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub(crate) rrset_type: Option<String>,
+}
+
+impl ProtectedRecord {
+    pub(crate) fn matches(&self, fqdn: &str, rrset_name: &str, rrset_type: &str) -> bool {
+        let matches = |pattern: &Option<String>, value: &str| match pattern {
+            Some(pattern) => Pattern::new(&pattern.to_lowercase())
+                .map(|pattern| pattern.matches(&value.to_lowercase()))
+                .unwrap_or(false),
+            None => true,
+        };
+
+        matches(&self.fqdn, fqdn)
+            && matches(&self.name, rrset_name)
+            && matches(&self.rrset_type, rrset_type)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for pattern in [&self.fqdn, &self.name, &self.rrset_type]
+            .into_iter()
+            .flatten()
+        {
+            Pattern::new(pattern)
+                .map_err(|e| format!("config: protected pattern '{}': {}", pattern, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A context as displayed by `gandictl config get-contexts`
+#[derive(Serialize)]
+pub(crate) struct ContextEntry {
+    name: String,
+    current: bool,
+    #[serde(flatten)]
+    context: Context,
+}
+
+impl Resource for ContextEntry {
+    fn headers(_wide: bool) -> Vec<&'static str> {
+        vec!["CURRENT", "NAME", "ENDPOINT", "DOMAIN", "SHARING_ID"]
+    }
+
+    fn row(&self, _wide: bool) -> Vec<String> {
+        vec![
+            if self.current { "*" } else { "" }.to_owned(),
+            self.name.to_owned(),
+            self.context.endpoint.to_owned().unwrap_or_default(),
+            self.context.domain.to_owned().unwrap_or_default(),
+            self.context.sharing_id.to_owned().unwrap_or_default(),
+        ]
+    }
+
+    fn name(&self) -> String {
+        self.name.to_owned()
+    }
+}
+
+impl GandiCtlConfig {
+    /// Returns the config file path
+    ///
+    /// `$GANDICTL_CONFIG`, `$XDG_CONFIG_HOME/gandictl/config.yaml` or `$HOME/.config/gandictl/config.yaml`
+    pub(crate) fn path() -> Result<PathBuf, Box<dyn Error>> {
+        if let Ok(path) = env::var("GANDICTL_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+
+        let config_home = match env::var("XDG_CONFIG_HOME") {
+            Ok(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+            _ => PathBuf::from(env::var("HOME")?).join(".config"),
+        };
+
+        Ok(config_home.join("gandictl").join("config.yaml"))
+    }
+
+    /// Load the config file, an empty config is returned if the file doesn't exist
+    pub(crate) fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(GandiCtlConfig::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+
+        let config: GandiCtlConfig = serde_yaml::from_str(&content)
+            .map_err(|e| format!("config: {} ({})", e, path.display()))?;
+
+        for protected in &config.protected {
+            protected.validate()?;
+        }
+
+        Ok(config)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_yaml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// Returns the context selected by name or the current context.
+    ///
+    /// Without any context, the default one is used (prod and `GANDI_V5_PAT`).
+    pub(crate) fn context(&self, name: Option<&str>) -> Result<Context, String> {
+        match name.or(self.current_context.as_deref()) {
+            Some(name) => self
+                .contexts
+                .get(name)
+                .cloned()
+                .ok_or(format!("config: context {} not found", name)),
+            None => Ok(Context::default()),
+        }
+    }
+
+    pub(crate) fn use_context(&mut self, name: &str) -> Result<(), String> {
+        if !self.contexts.contains_key(name) {
+            return Err(format!("config: context {} not found", name));
+        }
+
+        self.current_context = Some(name.to_owned());
+
+        Ok(())
+    }
+
+    /// Create or update a context; only fields provided are changed
+    pub(crate) fn set_context(&mut self, name: &str, update: Context) {
+        let context = self.contexts.entry(name.to_owned()).or_default();
+
+        if update.endpoint.is_some() {
+            context.endpoint = update.endpoint;
+        }
+        if update.token.is_some() {
+            context.token = update.token;
+        }
+        if update.sharing_id.is_some() {
+            context.sharing_id = update.sharing_id;
+        }
+        if update.domain.is_some() {
+            context.domain = update.domain;
+        }
+    }
+
+    pub(crate) fn entries(&self) -> Vec<ContextEntry> {
+        self.contexts
+            .iter()
+            .map(|(name, context)| ContextEntry {
+                name: name.to_owned(),
+                current: self.current_context.as_ref() == Some(name),
+                context: context.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Returns the cache directory
+///
+/// `$XDG_CACHE_HOME/gandictl` or `$HOME/.cache/gandictl`
+pub(crate) fn cache_directory() -> Result<PathBuf, Box<dyn Error>> {
+    let cache_home = match env::var("XDG_CACHE_HOME") {
+        Ok(cache_home) if !cache_home.is_empty() => PathBuf::from(cache_home),
+        _ => PathBuf::from(env::var("HOME")?).join(".cache"),
+    };
+
+    Ok(cache_home.join("gandictl"))
+}
+
+/// Returns the state directory
+///
+/// `$XDG_STATE_HOME/gandictl` or `$HOME/.local/state/gandictl`
+pub(crate) fn state_directory() -> Result<PathBuf, Box<dyn Error>> {
+    let state_home = match env::var("XDG_STATE_HOME") {
+        Ok(state_home) if !state_home.is_empty() => PathBuf::from(state_home),
+        _ => PathBuf::from(env::var("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+
+    Ok(state_home.join("gandictl"))
+}
+
+/// Parse prod, sandbox or a custom url (http:// or https://)
+pub(crate) fn parse_endpoint(endpoint: &str) -> Result<Endpoint, String> {
+    match endpoint {
+        "prod" => Ok(Endpoint::Prod),
+        "sandbox" => Ok(Endpoint::Sandbox),
+        url if url.starts_with("https://") || url.starts_with("http://") => {
+            Ok(Endpoint::Custom(url.trim_end_matches('/').to_owned()))
+        }
+        _ => Err(format!(
+            "'{endpoint}' isn't prod, sandbox or an http(s):// url"
+        )),
+    }
+}
+
+/// Name of an endpoint in the caches: prod, sandbox or the url with `_` for other characters than alphanumerics
+pub(crate) fn endpoint_name(endpoint: &Endpoint) -> String {
+    match endpoint {
+        Endpoint::Prod => "prod".to_owned(),
+        Endpoint::Sandbox => "sandbox".to_owned(),
+        Endpoint::Custom(url) => url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect(),
+    }
+}
+
+/// Validate an endpoint provided on the command line
+pub(crate) fn endpoint_is_valid(endpoint: &str) -> Result<String, String> {
+    parse_endpoint(endpoint).map(|_| endpoint.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+current-context: personal
+contexts:
+  personal:
+    endpoint: prod
+    domain: example.org
+  company:
+    endpoint: https://api.example.net/v5/
+    token:
+      env: COMPANY_PAT
+    sharing-id: organization-id
+  keyring:
+    token:
+      keyring:
+        service: gandi
+        user: me
+protected:
+- name: "@"
+  type: NS
+- type: mx
+- name: "*._domainkey"
+  fqdn: "*.org"
+"#;
+
+    #[test]
+    fn parse_endpoints() {
+        assert!(matches!(parse_endpoint("prod"), Ok(Endpoint::Prod)));
+        assert!(matches!(parse_endpoint("sandbox"), Ok(Endpoint::Sandbox)));
+        assert!(
+            matches!(parse_endpoint("https://api.local/v5/"), Ok(Endpoint::Custom(url)) if url == "https://api.local/v5")
+        );
+        assert_eq!(
+            parse_endpoint("api.local").err().unwrap(),
+            "'api.local' isn't prod, sandbox or an http(s):// url"
+        );
+
+        assert_eq!(endpoint_name(&Endpoint::Sandbox), "sandbox");
+        assert_eq!(
+            endpoint_name(&parse_endpoint("https://api.local/v5").unwrap()),
+            "https___api_local_v5"
+        );
+    }
+
+    #[test]
+    fn select_context() {
+        let config: GandiCtlConfig = serde_yaml::from_str(CONFIG).unwrap();
+
+        let current = config.context(None).unwrap();
+        assert_eq!(current.domain.as_deref(), Some("example.org"));
+        assert_eq!(current.token, None);
+
+        let company = config.context(Some("company")).unwrap();
+        assert_eq!(
+            company.token,
+            Some(TokenSource::Env("COMPANY_PAT".to_owned()))
+        );
+        assert_eq!(company.sharing_id.as_deref(), Some("organization-id"));
+
+        let keyring = config.context(Some("keyring")).unwrap();
+        assert_eq!(
+            keyring.token,
+            Some(TokenSource::Keyring {
+                service: "gandi".to_owned(),
+                user: "me".to_owned()
+            })
+        );
+
+        assert_eq!(
+            config.context(Some("unknown")).unwrap_err(),
+            "config: context unknown not found"
+        );
+    }
+
+    #[test]
+    fn protected_records() {
+        let config: GandiCtlConfig = serde_yaml::from_str(CONFIG).unwrap();
+        let is_protected = |fqdn, rrset_name, rrset_type| {
+            config
+                .protected
+                .iter()
+                .any(|protected| protected.matches(fqdn, rrset_name, rrset_type))
+        };
+
+        assert!(is_protected("example.org", "@", "NS"));
+        assert!(!is_protected("example.org", "www", "NS"));
+        assert!(is_protected("example.net", "@", "MX"));
+        assert!(is_protected("example.org", "s1._domainkey", "TXT"));
+        assert!(!is_protected("example.net", "s1._domainkey", "TXT"));
+        assert!(!is_protected("example.org", "www", "A"));
+    }
+
+    #[test]
+    fn protected_invalid_pattern() {
+        let protected = ProtectedRecord {
+            name: Some("[".to_owned()),
+            ..Default::default()
+        };
+
+        assert!(protected.validate().is_err());
+    }
+
+    #[test]
+    fn default_context() {
+        let config = GandiCtlConfig::default();
+
+        assert_eq!(config.context(None).unwrap(), Context::default());
+    }
+
+    #[test]
+    fn use_and_set_context() {
+        let mut config: GandiCtlConfig = serde_yaml::from_str(CONFIG).unwrap();
+
+        assert!(config.use_context("unknown").is_err());
+        assert!(config.use_context("company").is_ok());
+        assert_eq!(config.current_context.as_deref(), Some("company"));
+
+        config.set_context(
+            "company",
+            Context {
+                domain: Some("example.net".to_owned()),
+                ..Default::default()
+            },
+        );
+
+        let company = config.context(None).unwrap();
+        assert_eq!(company.domain.as_deref(), Some("example.net"));
+        assert_eq!(company.sharing_id.as_deref(), Some("organization-id"));
+
+        let entries = config.entries();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].current);
+        assert_eq!(entries[0].name, "company");
+    }
+
+    #[test]
+    fn load_and_save() {
+        let path = env::temp_dir()
+            .join(format!("gandictl-config-{}", std::process::id()))
+            .join("config.yaml");
+
+        let config = GandiCtlConfig::load(&path).unwrap();
+        assert!(config.contexts.is_empty());
+
+        let mut config: GandiCtlConfig = serde_yaml::from_str(CONFIG).unwrap();
+        config.use_context("company").unwrap();
+        config.save(&path).unwrap();
+
+        let config = GandiCtlConfig::load(&path).unwrap();
+        assert_eq!(config.current_context.as_deref(), Some("company"));
+        assert_eq!(config.contexts.len(), 3);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
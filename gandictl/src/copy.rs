@@ -0,0 +1,288 @@
+use clap::ValueEnum;
+use futures::{stream, StreamExt, TryStreamExt};
+use gandi_v5_livedns_api::records::{add_values, Record};
+use std::error::Error;
+
+use crate::{
+    changes::{apply_all, diff, Change},
+    selection::Selection,
+    session::Session,
+};
+
+/// How copied records are combined with the records of the destination
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum Mode {
+    /// Add the values to the existing records of the destination
+    Merge,
+    /// Selected records of the destination become the copied records (others are deleted)
+    Replace,
+}
+
+/// Copy selected records of a domain to other domains
+///
+/// Changes are printed before being applied.
+pub(crate) async fn copy(
+    session: &Session,
+    from: String,
+    to: Vec<String>,
+    selection: Selection,
+    mode: Mode,
+    concurrency: usize,
+) -> Result<(), Box<dyn Error>> {
+    let from = session.fqdn(from)?;
+    let to = to
+        .into_iter()
+        .map(|fqdn| session.fqdn(fqdn))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if to.contains(&from) {
+        return Err(format!("copy: {} is both the source and a destination", from).into());
+    }
+
+    let source = copied(&selection, session.api.records(&from).await?);
+
+    if source.is_empty() {
+        return Err(format!("copy: no record selected ({})", from).into());
+    }
+
+    let plans: Vec<(String, Vec<Change>)> = stream::iter(to)
+        .map(|fqdn| {
+            let source = &source;
+            let selection = &selection;
+            let from = &from;
+
+            async move {
+                let current = copied(selection, session.api.records(&fqdn).await?);
+                let target = target(&current, &rewrite(source, from, &fqdn), mode);
+
+                Ok::<_, Box<dyn Error>>((fqdn, diff(&current, &target, mode == Mode::Replace)))
+            }
+        })
+        .buffered(concurrency)
+        .try_collect()
+        .await?;
+
+    let plans: Vec<_> = plans
+        .into_iter()
+        .filter(|(_, changes)| !changes.is_empty())
+        .collect();
+
+    if plans.is_empty() {
+        println!("Nothing to copy !");
+        return Ok(());
+    }
+
+    let mut count = 0;
+
+    for (fqdn, changes) in &plans {
+        println!("{}:", fqdn);
+
+        for change in changes {
+            let record = change.record();
+            session.protect(fqdn, &record.rrset_name, &record.rrset_type)?;
+
+            print!("{}", change);
+            count += 1;
+        }
+    }
+
+    if !session.yes && !session.dry_run {
+        session.ask(
+            &format!(
+                "Copy {} changes from {} to {} domains? [y/N] ",
+                count,
+                from,
+                plans.len()
+            ),
+            "Copy",
+        )?;
+    }
+
+    let applied = apply_all(session, &plans, concurrency).await;
+
+    if applied.changes > 0 {
+        session.notify(&format!(
+            "Copied {} changes from {} to {} domains !",
+            applied.changes, from, applied.domains
+        ));
+    }
+
+    match applied.errors.is_empty() {
+        true => Ok(()),
+        false => Err(applied.errors.join("\n").into()),
+    }
+}
+
+/// Selected records, without the NS and SOA records of the apex unless their type is selected
+///
+/// The delegation of a domain isn't copied, nor deleted in replace mode.
+fn copied(selection: &Selection, records: Vec<Record>) -> Vec<Record> {
+    selection
+        .filter(records)
+        .into_iter()
+        .filter(|record| {
+            record.rrset_name != "@"
+                || !matches!(record.rrset_type.as_str(), "NS" | "SOA")
+                || selection.has_type(&record.rrset_type)
+        })
+        .collect()
+}
+
+/// Records of the destination after the copy
+///
+/// Values of types with a single value (eg: CNAME) are replaced in merge mode.
+fn target(current: &[Record], copied: &[Record], mode: Mode) -> Vec<Record> {
+    copied
+        .iter()
+        .map(|record| {
+            let existing = current
+                .iter()
+                .find(|c| c.rrset_name == record.rrset_name && c.rrset_type == record.rrset_type);
+            let single_value = matches!(
+                record.rrset_type.as_str(),
+                "CNAME" | "ALIAS" | "DNAME" | "SOA"
+            );
+
+            match (mode, existing) {
+                (Mode::Merge, Some(existing)) if !single_value => Record {
+                    rrset_values: add_values(&existing.rrset_values, &record.rrset_values),
+                    ..record.to_owned()
+                },
+                _ => record.to_owned(),
+            }
+        })
+        .collect()
+}
+
+/// Records with the absolute targets in the source domain moved to the destination domain
+fn rewrite(records: &[Record], from: &str, to: &str) -> Vec<Record> {
+    records
+        .iter()
+        .map(|record| Record {
+            rrset_values: record
+                .rrset_values
+                .iter()
+                .map(|value| rewrite_value(&record.rrset_type, value, from, to))
+                .collect(),
+            ..record.to_owned()
+        })
+        .collect()
+}
+
+fn rewrite_value(rrset_type: &str, value: &str, from: &str, to: &str) -> String {
+    // position of the target in the value
+    let position = match rrset_type {
+        "CNAME" | "NS" | "ALIAS" | "DNAME" | "PTR" => 0,
+        "MX" => 1,
+        "SRV" => 3,
+        _ => return value.to_owned(),
+    };
+
+    value
+        .split(' ')
+        .enumerate()
+        .map(|(i, word)| match i == position {
+            true => rewrite_name(word, from, to),
+            false => word.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `x.{from}.` becomes `x.{to}.`, other names are unchanged
+fn rewrite_name(name: &str, from: &str, to: &str) -> String {
+    let lowercase = name.to_lowercase();
+    let origin = format!("{}.", from.to_lowercase());
+
+    if lowercase == origin {
+        return format!("{}.", to);
+    }
+
+    match lowercase.strip_suffix(&format!(".{}", origin)) {
+        Some(_) => format!("{}.{}.", &name[..name.len() - origin.len() - 1], to),
+        None => name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    #[test]
+    fn rewrite_targets() {
+        let records = vec![
+            record("@", "MX", &["10 mail.example.org.", "20 mx.gandi.net."]),
+            record("www", "CNAME", &["example.org."]),
+            record("_sip._tcp", "SRV", &["10 60 5060 SIP.Example.org."]),
+            record("shop", "CNAME", &["shop.notexample.org."]),
+            record("@", "TXT", &["\"v=spf1 include:example.org -all\""]),
+        ];
+
+        assert_eq!(
+            rewrite(&records, "example.org", "example.net"),
+            vec![
+                record("@", "MX", &["10 mail.example.net.", "20 mx.gandi.net."]),
+                record("www", "CNAME", &["example.net."]),
+                record("_sip._tcp", "SRV", &["10 60 5060 SIP.example.net."]),
+                record("shop", "CNAME", &["shop.notexample.org."]),
+                record("@", "TXT", &["\"v=spf1 include:example.org -all\""]),
+            ]
+        );
+    }
+
+    #[test]
+    fn target_modes() {
+        let current = vec![record("@", "MX", &["10 mail"])];
+        let copied = vec![
+            record("@", "MX", &["20 backup"]),
+            record("mail", "A", &["10.0.0.1"]),
+        ];
+
+        assert_eq!(
+            target(&current, &copied, Mode::Merge),
+            vec![
+                record("@", "MX", &["10 mail", "20 backup"]),
+                record("mail", "A", &["10.0.0.1"]),
+            ]
+        );
+        assert_eq!(target(&current, &copied, Mode::Replace), copied);
+
+        let current = vec![record("www", "CNAME", &["old.example.org."])];
+        let copied = vec![record("www", "CNAME", &["new.example.org."])];
+
+        assert_eq!(target(&current, &copied, Mode::Merge), copied);
+    }
+
+    #[test]
+    fn apex_delegation() {
+        let records = vec![
+            record("@", "NS", &["ns1.gandi.net."]),
+            record(
+                "@",
+                "SOA",
+                &["ns1.gandi.net. hostmaster.gandi.net. 1 2 3 4 5"],
+            ),
+            record("@", "MX", &["10 mail"]),
+            record("sub", "NS", &["ns.example.net."]),
+        ];
+
+        let all = Selection::build(vec![], vec![]).unwrap();
+        assert_eq!(
+            copied(&all, records.clone()),
+            vec![
+                record("@", "MX", &["10 mail"]),
+                record("sub", "NS", &["ns.example.net."]),
+            ]
+        );
+
+        let ns = Selection::build(vec![], vec!["ns".to_owned()]).unwrap();
+        assert_eq!(
+            copied(&ns, records),
+            vec![
+                record("@", "NS", &["ns1.gandi.net."]),
+                record("sub", "NS", &["ns.example.net."]),
+            ]
+        );
+    }
+}
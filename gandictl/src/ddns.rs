@@ -0,0 +1,269 @@
+mod discovery;
+mod stun;
+
+pub(crate) use discovery::Discovery;
+
+use gandi_v5_livedns_api::records::{Record, UpsertRecord};
+use std::{error::Error, fs, path::PathBuf, time::Duration};
+
+use crate::{cli::DdnsArgs, config::cache_directory, session::Session};
+use discovery::{Discoverer, Family};
+
+/// Update A and/or AAAA records with the public addresses, once or periodically (daemon)
+pub(crate) async fn ddns(args: DdnsArgs, session: &Session) -> Result<(), Box<dyn Error>> {
+    if args.daemon && session.dry_run {
+        return Err("ddns: --dry-run can't be used with --daemon".into());
+    }
+
+    let fqdn = session.fqdn(args.fqdn.to_owned())?;
+
+    let discoverer = Discoverer {
+        methods: args.discovery.to_owned(),
+        http_ipv4_urls: args.http_ipv4_url.to_owned(),
+        http_ipv6_urls: args.http_ipv6_url.to_owned(),
+        interface: args.interface.to_owned(),
+        stun_servers: args.stun_server.to_owned(),
+    };
+
+    let cache = Cache {
+        directory: match &args.cache_dir {
+            Some(directory) => directory.to_owned(),
+            None => Cache::default_directory()?,
+        },
+        max_age: args.cache_max_age,
+    };
+
+    if !args.daemon {
+        return update_all(&args, &fqdn, session, &discoverer, &cache).await;
+    }
+
+    loop {
+        if let Err(e) = update_all(&args, &fqdn, session, &discoverer, &cache).await {
+            eprintln!("{}", e);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(args.interval) => {},
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+async fn update_all(
+    args: &DdnsArgs,
+    fqdn: &str,
+    session: &Session,
+    discoverer: &Discoverer,
+    cache: &Cache,
+) -> Result<(), Box<dyn Error>> {
+    let mut errors = vec![];
+
+    for rrset_type in &args.rrset_types {
+        let update = update(
+            session,
+            discoverer,
+            cache,
+            fqdn,
+            &args.rrset_name,
+            rrset_type,
+            args.rrset_ttl,
+        );
+
+        if let Err(e) = update.await {
+            errors.push(e.to_string());
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("\n").into()),
+    }
+}
+
+/// Upsert the record only if the public address changed
+async fn update(
+    session: &Session,
+    discoverer: &Discoverer,
+    cache: &Cache,
+    fqdn: &str,
+    rrset_name: &str,
+    rrset_type: &str,
+    rrset_ttl: Option<u32>,
+) -> Result<(), Box<dyn Error>> {
+    session.protect(fqdn, rrset_name, rrset_type)?;
+
+    let family = Family::from_rrset_type(rrset_type)?;
+    let ip = discoverer.discover(family).await?.to_string();
+
+    let key = format!(
+        "{}_{}_{}_{}_{}",
+        session.endpoint,
+        fqdn,
+        rrset_name,
+        rrset_type,
+        rrset_ttl.map_or("default".to_owned(), |rrset_ttl| rrset_ttl.to_string())
+    );
+
+    if cache.get(&key).as_deref() == Some(ip.as_str()) {
+        println!(
+            "Record {}.{} type {} is up to date with ip {} (cache) !",
+            rrset_name, fqdn, rrset_type, ip
+        );
+
+        return Ok(());
+    }
+
+    let record = UpsertRecord {
+        rrset_values: vec![ip.to_owned()],
+        rrset_ttl,
+    };
+
+    let current = session
+        .api
+        .find_record_by_name_and_type(fqdn, rrset_name, rrset_type)
+        .await?;
+
+    if is_up_to_date(&current, &record) {
+        println!(
+            "Record {}.{} type {} is up to date with ip {} !",
+            rrset_name, fqdn, rrset_type, ip
+        );
+    } else {
+        session
+            .api
+            .upsert_record_by_name_and_type(fqdn, rrset_name, rrset_type, &record)
+            .await?;
+
+        session.notify(&format!(
+            "Record {}.{} type {} updated with ip {} !",
+            rrset_name, fqdn, rrset_type, ip
+        ));
+    }
+
+    if !session.dry_run {
+        cache.set(&key, &ip)?;
+    }
+
+    Ok(())
+}
+
+/// Returns true if the record has the same values and ttl (if provided)
+fn is_up_to_date(current: &Option<Record>, record: &UpsertRecord) -> bool {
+    match current {
+        Some(current) => {
+            current.rrset_values == record.rrset_values
+                && (record.rrset_ttl.is_none() || current.rrset_ttl == record.rrset_ttl)
+        }
+        None => false,
+    }
+}
+
+/// Last known public addresses, one file per record, trusted until `max_age`
+///
+/// The record is then compared again with Gandi, so a record modified or deleted elsewhere is repaired.
+struct Cache {
+    directory: PathBuf,
+    max_age: Duration,
+}
+
+impl Cache {
+    /// `$XDG_CACHE_HOME/gandictl/ddns` or `$HOME/.cache/gandictl/ddns`
+    fn default_directory() -> Result<PathBuf, Box<dyn Error>> {
+        Ok(cache_directory()?.join("ddns"))
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let path = self.directory.join(key);
+        let age = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()?
+            .elapsed()
+            .unwrap_or_default();
+
+        if age >= self.max_age {
+            return None;
+        }
+
+        fs::read_to_string(path).ok().map(|ip| ip.trim().to_owned())
+    }
+
+    fn set(&self, key: &str, ip: &str) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.directory.join(key), ip)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+    use std::env;
+
+    fn home(value: &str, rrset_ttl: Option<u32>) -> Record {
+        Record {
+            rrset_ttl,
+            ..record("home", "A", &[value])
+        }
+    }
+
+    #[test]
+    fn up_to_date() {
+        let upsert = UpsertRecord {
+            rrset_values: vec!["203.0.113.5".to_owned()],
+            rrset_ttl: Some(300),
+        };
+
+        assert!(is_up_to_date(
+            &Some(home("203.0.113.5", Some(300))),
+            &upsert
+        ));
+        assert!(!is_up_to_date(
+            &Some(home("203.0.113.5", Some(600))),
+            &upsert
+        ));
+        assert!(!is_up_to_date(
+            &Some(home("203.0.113.6", Some(300))),
+            &upsert
+        ));
+        assert!(!is_up_to_date(&None, &upsert));
+
+        let upsert = UpsertRecord {
+            rrset_ttl: None,
+            ..upsert
+        };
+
+        assert!(is_up_to_date(
+            &Some(home("203.0.113.5", Some(600))),
+            &upsert
+        ));
+    }
+
+    #[test]
+    fn cache() {
+        let cache = Cache {
+            directory: env::temp_dir().join(format!("gandictl-ddns-{}", std::process::id())),
+            max_age: Duration::from_secs(3600),
+        };
+
+        assert_eq!(cache.get("example.org_home_A"), None);
+
+        cache.set("example.org_home_A", "203.0.113.5").unwrap();
+
+        assert_eq!(
+            cache.get("example.org_home_A").as_deref(),
+            Some("203.0.113.5")
+        );
+
+        // the record is compared again with Gandi
+        let expired = Cache {
+            directory: cache.directory.clone(),
+            max_age: Duration::ZERO,
+        };
+
+        assert_eq!(expired.get("example.org_home_A"), None);
+
+        fs::remove_dir_all(&cache.directory).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+use clap::ValueEnum;
+use std::{
+    error::Error,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration,
+};
+
+use super::stun;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Methods to discover a public address
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum Discovery {
+    /// HTTP echo services
+    Http,
+    /// Address of a local network interface
+    Interface,
+    /// STUN binding request
+    Stun,
+}
+
+/// Address family
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    /// Returns the family of A (ipv4) or AAAA (ipv6) records
+    pub(crate) fn from_rrset_type(rrset_type: &str) -> Result<Self, String> {
+        match rrset_type {
+            "A" => Ok(Family::V4),
+            "AAAA" => Ok(Family::V6),
+            _ => Err(format!("ddns: type {} isn't A or AAAA", rrset_type)),
+        }
+    }
+
+    pub(crate) fn matches(&self, ip: &IpAddr) -> bool {
+        matches!(
+            (self, ip),
+            (Family::V4, IpAddr::V4(_)) | (Family::V6, IpAddr::V6(_))
+        )
+    }
+
+    fn unspecified(&self) -> IpAddr {
+        match self {
+            Family::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Family::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Family::V4 => f.write_str("ipv4"),
+            Family::V6 => f.write_str("ipv6"),
+        }
+    }
+}
+
+/// Discover public addresses with methods tried in order
+pub(crate) struct Discoverer {
+    pub(crate) methods: Vec<Discovery>,
+    pub(crate) http_ipv4_urls: Vec<String>,
+    pub(crate) http_ipv6_urls: Vec<String>,
+    pub(crate) interface: Option<String>,
+    pub(crate) stun_servers: Vec<String>,
+}
+
+impl Discoverer {
+    /// Returns the public address found by the first method that succeeds
+    pub(crate) async fn discover(&self, family: Family) -> Result<IpAddr, Box<dyn Error>> {
+        let mut errors = vec![];
+
+        for method in &self.methods {
+            let ip = match method {
+                Discovery::Http => self.http(family).await,
+                Discovery::Interface => self.interface(family),
+                Discovery::Stun => self.stun(family).await,
+            };
+
+            match ip {
+                Ok(ip) if family.matches(&ip) && is_global(&ip) => return Ok(ip),
+                Ok(ip) => errors.push(format!(
+                    "{:?}: {} isn't a public {} address",
+                    method, ip, family
+                )),
+                Err(e) => errors.push(format!("{:?}: {}", method, e)),
+            }
+        }
+
+        Err(format!(
+            "ddns: no public {} address found ({})",
+            family,
+            errors.join(", ")
+        )
+        .into())
+    }
+
+    async fn http(&self, family: Family) -> Result<IpAddr, Box<dyn Error>> {
+        let urls = match family {
+            Family::V4 => &self.http_ipv4_urls,
+            Family::V6 => &self.http_ipv6_urls,
+        };
+
+        // bind to an unspecified address to force the family
+        let client = reqwest::Client::builder()
+            .local_address(family.unspecified())
+            .timeout(HTTP_TIMEOUT)
+            .build()?;
+
+        let mut errors = vec![];
+
+        for url in urls {
+            let response = async {
+                let ip = client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+
+                Ok::<IpAddr, Box<dyn Error>>(ip.trim().parse()?)
+            };
+
+            match response.await {
+                Ok(ip) => return Ok(ip),
+                Err(e) => errors.push(format!("{} ({})", e, url)),
+            }
+        }
+
+        Err(errors.join(", ").into())
+    }
+
+    fn interface(&self, family: Family) -> Result<IpAddr, Box<dyn Error>> {
+        if_addrs::get_if_addrs()?
+            .into_iter()
+            .filter(|interface| match &self.interface {
+                Some(name) => &interface.name == name,
+                None => true,
+            })
+            .map(|interface| interface.ip())
+            .find(|ip| family.matches(ip) && is_global(ip))
+            .ok_or(
+                format!(
+                    "no public address on interface {}",
+                    self.interface.as_deref().unwrap_or("any")
+                )
+                .into(),
+            )
+    }
+
+    async fn stun(&self, family: Family) -> Result<IpAddr, Box<dyn Error>> {
+        let mut errors = vec![];
+
+        for server in &self.stun_servers {
+            match stun::public_address(server, family).await {
+                Ok(ip) => return Ok(ip),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+
+        Err(errors.join(", ").into())
+    }
+}
+
+/// Returns true if the address is publicly routable
+pub(crate) fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // shared address space (carrier-grade NAT)
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            let segment = ip.segments()[0];
+
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                // unique local
+                || (segment & 0xfe00) == 0xfc00
+                // link local
+                || (segment & 0xffc0) == 0xfe80
+                // documentation
+                || (segment == 0x2001 && ip.segments()[1] == 0x0db8))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn family_from_rrset_type() {
+        assert_eq!(Family::from_rrset_type("A"), Ok(Family::V4));
+        assert_eq!(Family::from_rrset_type("AAAA"), Ok(Family::V6));
+        assert!(Family::from_rrset_type("TXT").is_err());
+    }
+
+    #[test]
+    fn global_addresses() {
+        for ip in ["1.1.1.1", "2a00:1450:4007:80e::200e"] {
+            assert!(is_global(&ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in [
+            "10.0.0.1",
+            "192.168.1.1",
+            "127.0.0.1",
+            "169.254.0.1",
+            "100.64.0.1",
+            "192.0.2.1",
+            "::1",
+            "fd00::2",
+            "fe80::1",
+            "2001:db8::1",
+        ] {
+            assert!(!is_global(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn discover_without_method() {
+        let discoverer = Discoverer {
+            methods: vec![],
+            http_ipv4_urls: vec![],
+            http_ipv6_urls: vec![],
+            interface: None,
+            stun_servers: vec![],
+        };
+
+        let res = discoverer.discover(Family::V4).await;
+
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "ddns: no public ipv4 address found ()"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::hash_map::RandomState,
+    error::Error,
+    hash::{BuildHasher, Hasher},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::{Duration, SystemTime},
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+use super::discovery::Family;
+
+const MAGIC_COOKIE: u32 = 0x2112A442;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS_RESPONSE: u16 = 0x0101;
+const MAPPED_ADDRESS: u16 = 0x0001;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const STUN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Returns the public address seen by the STUN server (host:port) with a binding request (RFC 5389)
+pub(crate) async fn public_address(server: &str, family: Family) -> Result<IpAddr, Box<dyn Error>> {
+    let server = tokio::net::lookup_host(server)
+        .await?
+        .find(|address| family.matches(&address.ip()))
+        .ok_or(format!("stun: no {} address for {}", family, server))?;
+
+    let socket = UdpSocket::bind(match family {
+        Family::V4 => "0.0.0.0:0",
+        Family::V6 => "[::]:0",
+    })
+    .await?;
+
+    let transaction_id = transaction_id();
+
+    socket
+        .send_to(&binding_request(&transaction_id), server)
+        .await?;
+
+    let mut response = [0u8; 512];
+    let (len, _) = timeout(STUN_TIMEOUT, socket.recv_from(&mut response))
+        .await
+        .map_err(|_| format!("stun: no response from {}", server))??;
+
+    Ok(parse_binding_response(&response[..len], &transaction_id)?)
+}
+
+fn transaction_id() -> [u8; 12] {
+    let random = |seed: u128| {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(seed);
+        hasher.finish()
+    };
+
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    let mut transaction_id = [0u8; 12];
+    transaction_id[..8].copy_from_slice(&random(nanos).to_be_bytes());
+    transaction_id[8..].copy_from_slice(&random(nanos + 1).to_be_bytes()[..4]);
+    transaction_id
+}
+
+fn binding_request(transaction_id: &[u8; 12]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(20);
+
+    request.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    // no attributes
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(transaction_id);
+
+    request
+}
+
+/// Returns the (XOR-)MAPPED-ADDRESS of a binding success response
+fn parse_binding_response(response: &[u8], transaction_id: &[u8; 12]) -> Result<IpAddr, String> {
+    let invalid = || "stun: invalid binding response".to_owned();
+
+    if response.len() < 20
+        || response[0..2] != BINDING_SUCCESS_RESPONSE.to_be_bytes()
+        || response[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || response[8..20] != transaction_id[..]
+    {
+        return Err(invalid());
+    }
+
+    let length = u16::from_be_bytes([response[2], response[3]]) as usize;
+    let attributes = response.get(20..20 + length).ok_or_else(invalid)?;
+
+    let mut mapped_address = None;
+    let mut offset = 0;
+
+    while offset + 4 <= attributes.len() {
+        let kind = u16::from_be_bytes([attributes[offset], attributes[offset + 1]]);
+        let len = u16::from_be_bytes([attributes[offset + 2], attributes[offset + 3]]) as usize;
+        let value = attributes
+            .get(offset + 4..offset + 4 + len)
+            .ok_or_else(invalid)?;
+
+        match kind {
+            XOR_MAPPED_ADDRESS => return parse_address(value, Some(transaction_id)),
+            MAPPED_ADDRESS => mapped_address = Some(parse_address(value, None)?),
+            _ => {}
+        }
+
+        // attributes are padded to 4 bytes
+        offset += 4 + len.div_ceil(4) * 4;
+    }
+
+    mapped_address.ok_or("stun: no mapped address in the binding response".to_owned())
+}
+
+/// Parse a MAPPED-ADDRESS or a XOR-MAPPED-ADDRESS (with the transaction id) attribute
+fn parse_address(value: &[u8], xor: Option<&[u8; 12]>) -> Result<IpAddr, String> {
+    let mut key = [0u8; 16];
+    if let Some(transaction_id) = xor {
+        key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        key[4..].copy_from_slice(transaction_id);
+    }
+
+    let address = |len: usize| -> Result<Vec<u8>, String> {
+        let address = value
+            .get(4..4 + len)
+            .ok_or("stun: invalid mapped address")?;
+
+        Ok(address
+            .iter()
+            .zip(key)
+            .map(|(byte, key)| byte ^ key)
+            .collect())
+    };
+
+    match value.get(1) {
+        Some(0x01) => {
+            let address: [u8; 4] = address(4)?.try_into().map_err(|_| "stun: invalid ipv4")?;
+            Ok(IpAddr::V4(Ipv4Addr::from(address)))
+        }
+        Some(0x02) => {
+            let address: [u8; 16] = address(16)?.try_into().map_err(|_| "stun: invalid ipv6")?;
+            Ok(IpAddr::V6(Ipv6Addr::from(address)))
+        }
+        _ => Err("stun: unknown address family".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSACTION_ID: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+    fn response(attributes: &[u8]) -> Vec<u8> {
+        let mut response = vec![];
+        response.extend_from_slice(&BINDING_SUCCESS_RESPONSE.to_be_bytes());
+        response.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        response.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        response.extend_from_slice(&TRANSACTION_ID);
+        response.extend_from_slice(attributes);
+        response
+    }
+
+    #[test]
+    fn request() {
+        let request = binding_request(&TRANSACTION_ID);
+
+        assert_eq!(request.len(), 20);
+        assert_eq!(request[0..4], [0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(request[4..8], [0x21, 0x12, 0xA4, 0x42]);
+        assert_eq!(request[8..], TRANSACTION_ID);
+    }
+
+    #[test]
+    fn xor_mapped_ipv4() {
+        // 203.0.113.5 xor 0x2112A442
+        let attributes = [
+            0x00,
+            0x20,
+            0x00,
+            0x08,
+            0x00,
+            0x01,
+            0x00,
+            0x00,
+            203 ^ 0x21,
+            0x12,
+            113 ^ 0xA4,
+            5 ^ 0x42,
+        ];
+
+        let address = parse_binding_response(&response(&attributes), &TRANSACTION_ID);
+
+        assert_eq!(address, Ok("203.0.113.5".parse().unwrap()));
+    }
+
+    #[test]
+    fn xor_mapped_ipv6() {
+        let ip: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let mut key = [0u8; 16];
+        key[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        key[4..].copy_from_slice(&TRANSACTION_ID);
+
+        let mut attributes = vec![0x00, 0x20, 0x00, 0x14, 0x00, 0x02, 0x00, 0x00];
+        attributes.extend(ip.octets().iter().zip(key).map(|(byte, key)| byte ^ key));
+
+        let address = parse_binding_response(&response(&attributes), &TRANSACTION_ID);
+
+        assert_eq!(address, Ok(IpAddr::V6(ip)));
+    }
+
+    #[test]
+    fn mapped_ipv4_after_unknown_attribute() {
+        let attributes = [
+            0x80, 0x22, 0x00, 0x03, b'a', b'b', b'c', 0x00, // SOFTWARE, padded
+            0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x00, 198, 51, 100, 7,
+        ];
+
+        let address = parse_binding_response(&response(&attributes), &TRANSACTION_ID);
+
+        assert_eq!(address, Ok("198.51.100.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn invalid_response() {
+        let mut other_transaction = response(&[]);
+        other_transaction[19] = 0;
+
+        assert!(parse_binding_response(&other_transaction, &TRANSACTION_ID).is_err());
+        assert!(parse_binding_response(&response(&[]), &TRANSACTION_ID).is_err());
+        assert!(parse_binding_response(&[0u8; 10], &TRANSACTION_ID).is_err());
+    }
+}
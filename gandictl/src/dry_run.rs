@@ -0,0 +1,162 @@
+use gandi_v5_livedns_api::{
+    records::{Record, UpsertRecord},
+    PlannedRequest,
+};
+use std::{collections::HashMap, error::Error};
+
+use crate::{output::render, session::Session};
+
+/// The record targeted by a request: (fqdn, rrset_name, rrset_type)
+type RecordKey = (String, String, String);
+
+/// Print requests recorded in dry run mode with the record before and after each of them.
+///
+/// The record before the first request is read from Gandi Api.
+pub(crate) async fn report(session: &Session) -> Result<(), Box<dyn Error>> {
+    let mut records: HashMap<RecordKey, Option<Record>> = HashMap::new();
+
+    for request in session.api.planned_requests() {
+        println!("{} {}", request.method, request.url);
+
+        if let Some(body) = &request.body {
+            println!("{}", body);
+        }
+
+        let Some(key) = record_key(&request.url) else {
+            continue;
+        };
+
+        let before = match records.get(&key) {
+            Some(record) => record.clone(),
+            None => {
+                let (fqdn, rrset_name, rrset_type) = &key;
+
+                session
+                    .api
+                    .find_record_by_name_and_type(fqdn, rrset_name, rrset_type)
+                    .await?
+            }
+        };
+
+        let after = record_after(&request, &key, &before)?;
+
+        if let Some(warning) = warning(&request, &before) {
+            println!("warning: {}", warning);
+        }
+
+        println!("--- before");
+        print_record(session, &before)?;
+        println!("+++ after");
+        print_record(session, &after)?;
+        println!();
+
+        records.insert(key, after);
+    }
+
+    Ok(())
+}
+
+fn print_record(session: &Session, record: &Option<Record>) -> Result<(), Box<dyn Error>> {
+    match record {
+        Some(record) => println!("{}", render(record, &session.output)?.trim_end()),
+        None => println!("(none)"),
+    }
+
+    Ok(())
+}
+
+/// Returns the record targeted by a request on `/livedns/domains/{fqdn}/records/{rrset_name}/{rrset_type}`
+fn record_key(url: &str) -> Option<RecordKey> {
+    let path = url.split('?').next()?;
+    let (_, path) = path.split_once("/livedns/domains/")?;
+
+    match path.split('/').collect::<Vec<_>>()[..] {
+        [fqdn, "records", rrset_name, rrset_type] => Some((
+            fqdn.to_owned(),
+            rrset_name.to_owned(),
+            rrset_type.to_owned(),
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the record as it would be after the request
+fn record_after(
+    request: &PlannedRequest,
+    (_, rrset_name, rrset_type): &RecordKey,
+    before: &Option<Record>,
+) -> Result<Option<Record>, Box<dyn Error>> {
+    match (request.method.as_str(), &request.body) {
+        ("POST" | "PUT", Some(body)) => {
+            let record: UpsertRecord = serde_json::from_str(body)?;
+
+            Ok(Some(Record {
+                rrset_name: rrset_name.to_owned(),
+                rrset_type: rrset_type.to_owned(),
+                rrset_values: record.rrset_values,
+                rrset_ttl: record.rrset_ttl,
+            }))
+        }
+        ("DELETE", _) => Ok(None),
+        _ => Ok(before.clone()),
+    }
+}
+
+/// Returns why the request would fail
+fn warning(request: &PlannedRequest, before: &Option<Record>) -> Option<&'static str> {
+    match (request.method.as_str(), before) {
+        ("POST", Some(_)) => Some("the record already exists, the request would fail"),
+        ("DELETE", None) => Some("the record doesn't exist, the request would fail"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    fn request(method: &str, body: Option<&str>) -> PlannedRequest {
+        PlannedRequest {
+            method: method.to_owned(),
+            url: "https://api.gandi.net/v5/livedns/domains/example.org/records/www/A?sharing_id=id"
+                .to_owned(),
+            body: body.map(str::to_owned),
+        }
+    }
+
+    fn key() -> RecordKey {
+        ("example.org".to_owned(), "www".to_owned(), "A".to_owned())
+    }
+
+    #[test]
+    fn record_key_from_url() {
+        assert_eq!(record_key(&request("PUT", None).url), Some(key()));
+        assert_eq!(
+            record_key("https://api.gandi.net/v5/livedns/domains/example.org/records"),
+            None
+        );
+    }
+
+    #[test]
+    fn record_after_requests() {
+        let before = Some(record("www", "A", &["10.0.0.1"]));
+        let body = r#"{"rrset_values":["10.0.0.2"],"rrset_ttl":300}"#;
+
+        let after = record_after(&request("PUT", Some(body)), &key(), &before).unwrap();
+        assert_eq!(after, Some(record("www", "A", &["10.0.0.2"])));
+
+        let after = record_after(&request("DELETE", None), &key(), &before).unwrap();
+        assert_eq!(after, None);
+    }
+
+    #[test]
+    fn warnings() {
+        let before = Some(record("www", "A", &["10.0.0.1"]));
+
+        assert!(warning(&request("POST", Some("{}")), &before).is_some());
+        assert!(warning(&request("POST", Some("{}")), &None).is_none());
+        assert!(warning(&request("DELETE", None), &None).is_some());
+        assert!(warning(&request("PUT", Some("{}")), &None).is_none());
+    }
+}
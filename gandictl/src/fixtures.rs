@@ -0,0 +1,11 @@
+use gandi_v5_livedns_api::records::Record;
+
+/// Record with a ttl of 300 seconds
+pub(crate) fn record(rrset_name: &str, rrset_type: &str, values: &[&str]) -> Record {
+    Record {
+        rrset_name: rrset_name.to_owned(),
+        rrset_type: rrset_type.to_owned(),
+        rrset_values: values.iter().map(|value| value.to_string()).collect(),
+        rrset_ttl: Some(300),
+    }
+}
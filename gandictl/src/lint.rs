@@ -0,0 +1,991 @@
+use clap::ValueEnum;
+use gandi_v5_livedns_api::records::Record;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt, fs,
+    path::Path,
+};
+
+use crate::{
+    cli::GlobalOptions,
+    config::GandiCtlConfig,
+    output::{handler, Output, Resource},
+    session::Session,
+};
+
+/// Max number of DNS lookups of a SPF record, including the lookups of included records (RFC 7208)
+const SPF_MAX_LOOKUPS: usize = 10;
+
+/// Records of other domains, by fqdn
+type Zones = BTreeMap<String, Vec<Record>>;
+
+/// Check of a TXT record returning an info or an error
+type TxtCheck = fn(&str) -> Result<Option<String>, String>;
+
+/// Severity of a finding
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => f.write_str(value.get_name()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A problem found in the records of a zone
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct Finding {
+    pub(crate) severity: Severity,
+    /// Identifier of the check
+    pub(crate) rule: &'static str,
+    pub(crate) rrset_name: String,
+    pub(crate) rrset_type: String,
+    pub(crate) message: String,
+}
+
+impl Resource for Finding {
+    fn headers(_wide: bool) -> Vec<&'static str> {
+        vec!["SEVERITY", "RULE", "NAME", "TYPE", "MESSAGE"]
+    }
+
+    fn row(&self, _wide: bool) -> Vec<String> {
+        vec![
+            self.severity.to_string(),
+            self.rule.to_owned(),
+            self.rrset_name.to_owned(),
+            self.rrset_type.to_owned(),
+            self.message.to_owned(),
+        ]
+    }
+
+    fn name(&self) -> String {
+        format!("{}/{}/{}", self.rule, self.rrset_name, self.rrset_type)
+    }
+}
+
+/// Lint records of a domain and print the findings
+pub(crate) async fn lint(
+    session: &Session,
+    fqdn: String,
+    fail_on: Severity,
+) -> Result<(), Box<dyn Error>> {
+    let fqdn = session.fqdn(fqdn)?;
+    let records = session.api.records(&fqdn).await?;
+    let zones = included_zones(session, &fqdn, &records).await?;
+
+    report(&fqdn, &records, &zones, &session.output, fail_on)
+}
+
+/// Records of the domains of the account holding SPF records included by the zone
+async fn included_zones(
+    session: &Session,
+    fqdn: &str,
+    records: &[Record],
+) -> Result<Zones, Box<dyn Error>> {
+    let domains: Vec<String> = session
+        .api
+        .domains()
+        .await?
+        .into_iter()
+        .map(|domain| domain.fqdn)
+        .filter(|domain| !domain.eq_ignore_ascii_case(fqdn))
+        .collect();
+    let mut zones = Zones::new();
+
+    // included zones can include other zones
+    loop {
+        let targets = Zone::new(fqdn, records, &zones).spf_unresolved();
+        let missing: Vec<&String> = domains
+            .iter()
+            .filter(|domain| !zones.contains_key(*domain))
+            .filter(|domain| {
+                targets
+                    .iter()
+                    .any(|target| relative(target, domain).is_some())
+            })
+            .collect();
+
+        if missing.is_empty() {
+            return Ok(zones);
+        }
+
+        for domain in missing {
+            zones.insert(domain.to_owned(), session.api.records(domain).await?);
+        }
+    }
+}
+
+/// Lint records of a yaml or json file and print the findings, Gandi Api isn't used
+///
+/// SPF records included from other domains can't be resolved.
+pub(crate) fn lint_file(
+    options: &GlobalOptions,
+    fqdn: String,
+    file: &Path,
+    fail_on: Severity,
+) -> Result<(), Box<dyn Error>> {
+    let fqdn = match fqdn.as_str() {
+        "." => GandiCtlConfig::load(&GandiCtlConfig::path()?)?
+            .context(options.context.as_deref())?
+            .domain
+            .ok_or("config: '.' requires a default domain in the context")?,
+        _ => fqdn,
+    };
+
+    let records: Vec<Record> = serde_yaml::from_str(
+        &fs::read_to_string(file).map_err(|e| format!("lint: {} ({})", e, file.display()))?,
+    )?;
+
+    report(&fqdn, &records, &Zones::new(), &options.output, fail_on)
+}
+
+/// Print the findings, an error is returned if a finding is at least as severe as `fail_on`
+fn report(
+    fqdn: &str,
+    records: &[Record],
+    zones: &Zones,
+    output: &Output,
+    fail_on: Severity,
+) -> Result<(), Box<dyn Error>> {
+    let findings = check(fqdn, records, zones);
+    let failures = findings
+        .iter()
+        .filter(|finding| finding.severity >= fail_on)
+        .count();
+
+    handler(findings, output)?;
+
+    match failures {
+        0 => Ok(()),
+        failures => Err(format!(
+            "lint: {} findings of severity {} or more ({})",
+            failures, fail_on, fqdn
+        )
+        .into()),
+    }
+}
+
+/// Returns the findings of every check, most severe first
+///
+/// SPF records included from `zones` are followed to count their lookups.
+pub(crate) fn check(fqdn: &str, records: &[Record], zones: &Zones) -> Vec<Finding> {
+    let zone = Zone::new(fqdn, records, zones);
+
+    let mut findings = vec![];
+    findings.extend(zone.cname_conflicts());
+    findings.extend(zone.targets());
+    findings.extend(zone.spf());
+    findings.extend(zone.dmarc_dkim());
+    findings.extend(zone.caa());
+    findings.extend(zone.ttls());
+
+    findings.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.rrset_name.cmp(&b.rrset_name))
+            .then_with(|| a.rule.cmp(b.rule))
+    });
+
+    findings
+}
+
+/// Records of a zone grouped by name
+struct Zone<'a> {
+    fqdn: &'a str,
+    names: BTreeMap<&'a str, Vec<&'a Record>>,
+    /// Other domains, to follow SPF includes
+    others: &'a Zones,
+}
+
+impl<'a> Zone<'a> {
+    fn new(fqdn: &'a str, records: &'a [Record], others: &'a Zones) -> Self {
+        let mut names: BTreeMap<&str, Vec<&Record>> = BTreeMap::new();
+
+        for record in records {
+            names.entry(&record.rrset_name).or_default().push(record);
+        }
+
+        Zone {
+            fqdn,
+            names,
+            others,
+        }
+    }
+
+    fn records(&self) -> impl Iterator<Item = &'a Record> + '_ {
+        self.names.values().flatten().copied()
+    }
+
+    fn types(&self, name: &str) -> BTreeSet<&'a str> {
+        self.names
+            .get(name)
+            .map(|records| {
+                records
+                    .iter()
+                    .map(|record| record.rrset_type.as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the name of a target in the zone, `None` if the target is outside of the zone
+    fn in_zone(&self, target: &str) -> Option<String> {
+        let Some(absolute) = target.strip_suffix('.') else {
+            // relative to the zone
+            return Some(target.to_owned());
+        };
+
+        relative(absolute, self.fqdn)
+    }
+
+    /// Returns the SPF records of a domain, `None` if the domain isn't in the zone or in the other domains
+    fn spf_records(&self, domain: &str) -> Option<Vec<String>> {
+        let records: Vec<&Record> = match relative(domain, self.fqdn) {
+            Some(name) => self.names.get(name.as_str()).cloned().unwrap_or_default(),
+            None => {
+                let (fqdn, records) = self
+                    .others
+                    .iter()
+                    .filter(|(fqdn, _)| relative(domain, fqdn).is_some())
+                    .max_by_key(|(fqdn, _)| fqdn.len())?;
+                let name = relative(domain, fqdn)?;
+
+                records
+                    .iter()
+                    .filter(|record| record.rrset_name.eq_ignore_ascii_case(&name))
+                    .collect()
+            }
+        };
+
+        Some(spf_values(records.into_iter()))
+    }
+
+    /// Number of DNS lookups of a SPF record, included and redirected records are followed
+    ///
+    /// The includes and redirects which can't be followed are added to `unresolved`.
+    fn spf_lookups(
+        &self,
+        spf: &str,
+        path: &mut Vec<String>,
+        unresolved: &mut BTreeSet<String>,
+    ) -> usize {
+        let mut lookups = 0;
+
+        for term in spf.split_whitespace().skip(1) {
+            let term = term.trim_start_matches(['+', '-', '~', '?']).to_lowercase();
+            let mechanism = term.split([':', '/', '=']).next().unwrap_or_default();
+
+            if !matches!(
+                mechanism,
+                "include" | "a" | "mx" | "ptr" | "exists" | "redirect"
+            ) {
+                continue;
+            }
+
+            lookups += 1;
+
+            if !matches!(mechanism, "include" | "redirect") {
+                continue;
+            }
+
+            let domain = term
+                .get(mechanism.len() + 1..)
+                .unwrap_or_default()
+                .trim_end_matches('.')
+                .to_owned();
+
+            // a loop is a permanent error of the record, it isn't counted again
+            if path.contains(&domain) {
+                continue;
+            }
+
+            // macros are expanded at evaluation
+            let included = match domain.contains('%') {
+                true => None,
+                false => self.spf_records(&domain),
+            };
+
+            match included.as_deref() {
+                Some([included]) => {
+                    path.push(domain);
+                    lookups += self.spf_lookups(included, path, unresolved);
+                    path.pop();
+                }
+                _ => {
+                    unresolved.insert(term);
+                }
+            }
+        }
+
+        lookups
+    }
+
+    /// Includes and redirects of the SPF records of the zone which can't be followed
+    fn spf_unresolved(&self) -> BTreeSet<String> {
+        let mut unresolved = BTreeSet::new();
+
+        for records in self.names.values() {
+            for spf in spf_values(records.iter().copied()) {
+                self.spf_lookups(&spf, &mut vec![], &mut unresolved);
+            }
+        }
+
+        unresolved
+            .iter()
+            .filter_map(|term| term.split_once([':', '=']))
+            .map(|(_, domain)| domain.trim_end_matches('.').to_owned())
+            .collect()
+    }
+
+    /// Returns true if the name has records, directly or with a wildcard
+    fn exists(&self, name: &str) -> bool {
+        if self.names.contains_key(name) {
+            return true;
+        }
+
+        let mut labels = name.split('.');
+
+        labels.next().is_some() && {
+            let parent: Vec<_> = labels.collect();
+
+            match parent.is_empty() {
+                true => self.names.contains_key("*"),
+                false => self
+                    .names
+                    .contains_key(format!("*.{}", parent.join(".")).as_str()),
+            }
+        }
+    }
+
+    fn cname_conflicts(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        for name in self.names.keys() {
+            let types = self.types(name);
+
+            if !types.contains("CNAME") {
+                continue;
+            }
+
+            if *name == "@" {
+                findings.push(finding(
+                    Severity::Error,
+                    "cname-apex",
+                    name,
+                    "CNAME",
+                    "CNAME isn't allowed at the apex, use ALIAS".to_owned(),
+                ));
+            }
+
+            let others: Vec<_> = types.iter().filter(|t| **t != "CNAME").copied().collect();
+
+            if !others.is_empty() {
+                findings.push(finding(
+                    Severity::Error,
+                    "cname-conflict",
+                    name,
+                    "CNAME",
+                    format!("CNAME coexists with {}", others.join(", ")),
+                ));
+            }
+        }
+
+        findings
+    }
+
+    /// MX, NS, SRV and CNAME targets in the zone must exist and MX and NS can't be a CNAME
+    fn targets(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        for record in self.records() {
+            let position = match record.rrset_type.as_str() {
+                "CNAME" | "NS" => 0,
+                "MX" => 1,
+                "SRV" => 3,
+                _ => continue,
+            };
+
+            for value in &record.rrset_values {
+                let Some(target) = value.split_whitespace().nth(position) else {
+                    continue;
+                };
+
+                // null MX (RFC 7505) or a target outside of the zone
+                let Some(name) = self.in_zone(target).filter(|_| target != ".") else {
+                    continue;
+                };
+
+                if !self.exists(&name) {
+                    findings.push(finding(
+                        Severity::Warning,
+                        "dangling-target",
+                        &record.rrset_name,
+                        &record.rrset_type,
+                        format!("{} doesn't exist in the zone", target),
+                    ));
+                } else if matches!(record.rrset_type.as_str(), "MX" | "NS")
+                    && self.types(&name).contains("CNAME")
+                {
+                    findings.push(finding(
+                        Severity::Error,
+                        "target-cname",
+                        &record.rrset_name,
+                        &record.rrset_type,
+                        format!("{} target {} is a CNAME", record.rrset_type, target),
+                    ));
+                }
+            }
+        }
+
+        findings
+    }
+
+    fn spf(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        for (name, records) in &self.names {
+            let mut spf = vec![];
+
+            for record in records {
+                match record.rrset_type.as_str() {
+                    "SPF" => findings.push(finding(
+                        Severity::Warning,
+                        "spf-type",
+                        name,
+                        "SPF",
+                        "SPF type is obsolete (RFC 7208), use TXT".to_owned(),
+                    )),
+                    "TXT" => spf.extend(spf_values([*record].into_iter())),
+                    _ => {}
+                }
+            }
+
+            if spf.len() > 1 {
+                findings.push(finding(
+                    Severity::Error,
+                    "spf-multiple",
+                    name,
+                    "TXT",
+                    format!("{} SPF records, only one is allowed", spf.len()),
+                ));
+            }
+
+            for spf in spf {
+                let mut unresolved = BTreeSet::new();
+                let lookups = self.spf_lookups(&spf, &mut vec![], &mut unresolved);
+
+                if lookups > SPF_MAX_LOOKUPS {
+                    findings.push(finding(
+                        Severity::Error,
+                        "spf-lookups",
+                        name,
+                        "TXT",
+                        format!("{} DNS lookups, the limit is {}", lookups, SPF_MAX_LOOKUPS),
+                    ));
+                }
+
+                for term in unresolved {
+                    findings.push(finding(
+                        Severity::Info,
+                        "spf-unresolved-include",
+                        name,
+                        "TXT",
+                        format!(
+                            "{} isn't a SPF record of the zone or of the account, its lookups aren't counted",
+                            term
+                        ),
+                    ));
+                }
+            }
+        }
+
+        findings
+    }
+
+    fn dmarc_dkim(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        for record in self.records().filter(|record| record.rrset_type == "TXT") {
+            let name = record.rrset_name.as_str();
+
+            let (rule, check): (_, TxtCheck) = if name == "_dmarc" || name.starts_with("_dmarc.") {
+                ("dmarc-syntax", dmarc)
+            } else if name.contains("._domainkey") {
+                ("dkim-syntax", dkim)
+            } else {
+                continue;
+            };
+
+            for value in &record.rrset_values {
+                match check(&txt(value)) {
+                    Ok(None) => {}
+                    Ok(Some(info)) => {
+                        findings.push(finding(Severity::Info, rule, name, "TXT", info))
+                    }
+                    Err(e) => findings.push(finding(Severity::Error, rule, name, "TXT", e)),
+                }
+            }
+        }
+
+        findings
+    }
+
+    fn caa(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        for record in self.records().filter(|record| record.rrset_type == "CAA") {
+            for value in &record.rrset_values {
+                if let Err((severity, e)) = caa(value) {
+                    findings.push(finding(
+                        severity,
+                        "caa-syntax",
+                        &record.rrset_name,
+                        "CAA",
+                        format!("{} ({})", e, value),
+                    ));
+                }
+            }
+        }
+
+        findings
+    }
+
+    fn ttls(&self) -> Vec<Finding> {
+        let mut findings = vec![];
+
+        for (name, records) in &self.names {
+            let ttls: BTreeSet<_> = records.iter().map(|record| record.rrset_ttl).collect();
+
+            if ttls.len() > 1 {
+                let ttls: Vec<_> = ttls
+                    .iter()
+                    .map(|ttl| match ttl {
+                        Some(ttl) => ttl.to_string(),
+                        None => "default".to_owned(),
+                    })
+                    .collect();
+
+                findings.push(finding(
+                    Severity::Info,
+                    "ttl-inconsistent",
+                    name,
+                    "",
+                    format!("records have different ttls: {}", ttls.join(", ")),
+                ));
+            }
+        }
+
+        findings
+    }
+}
+
+fn finding(
+    severity: Severity,
+    rule: &'static str,
+    rrset_name: &str,
+    rrset_type: &str,
+    message: String,
+) -> Finding {
+    Finding {
+        severity,
+        rule,
+        rrset_name: rrset_name.to_owned(),
+        rrset_type: rrset_type.to_owned(),
+        message,
+    }
+}
+
+/// Returns the text of a TXT value (quoted strings are concatenated)
+fn txt(value: &str) -> String {
+    if !value.starts_with('"') {
+        return value.to_owned();
+    }
+
+    let mut text = String::new();
+    let mut quoted = false;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', _) => quoted = !quoted,
+            ('\\', true) => text.extend(chars.next()),
+            (c, true) => text.push(c),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+/// Returns the name of a domain relative to a zone (`@` for the apex), `None` if the domain is outside of the zone
+fn relative(domain: &str, fqdn: &str) -> Option<String> {
+    if domain.eq_ignore_ascii_case(fqdn) {
+        return Some("@".to_owned());
+    }
+
+    domain
+        .to_lowercase()
+        .strip_suffix(&format!(".{}", fqdn.to_lowercase()))
+        .map(str::to_owned)
+}
+
+/// SPF values of TXT records
+fn spf_values<'a>(records: impl Iterator<Item = &'a Record>) -> Vec<String> {
+    records
+        .filter(|record| record.rrset_type == "TXT")
+        .flat_map(|record| &record.rrset_values)
+        .map(|value| txt(value))
+        .filter(|txt| txt.to_lowercase().starts_with("v=spf1"))
+        .collect()
+}
+
+/// Parse `tag=value; ...`
+fn tags(text: &str) -> Result<Vec<(String, String)>, String> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| match tag.split_once('=') {
+            Some((name, value)) => Ok((name.trim().to_lowercase(), value.trim().to_owned())),
+            None => Err(format!("'{}' isn't a tag=value", tag)),
+        })
+        .collect()
+}
+
+/// Check a DMARC record (RFC 7489)
+fn dmarc(text: &str) -> Result<Option<String>, String> {
+    let tags = tags(text)?;
+    let policies = ["none", "quarantine", "reject"];
+
+    match tags.first() {
+        Some((name, value)) if name == "v" && value == "DMARC1" => {}
+        _ => return Err("the first tag must be v=DMARC1".to_owned()),
+    }
+
+    if !tags.iter().any(|(name, _)| name == "p") {
+        return Err("the policy (p) is missing".to_owned());
+    }
+
+    for (name, value) in &tags {
+        let valid = match name.as_str() {
+            "p" | "sp" => policies.contains(&value.as_str()),
+            "adkim" | "aspf" => value == "r" || value == "s",
+            "pct" => value.parse::<u8>().is_ok_and(|pct| pct <= 100),
+            "rua" | "ruf" => value
+                .split(',')
+                .all(|uri| uri.trim().to_lowercase().starts_with("mailto:")),
+            _ => true,
+        };
+
+        if !valid {
+            return Err(format!("invalid {}={}", name, value));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Check a DKIM key record (RFC 6376)
+fn dkim(text: &str) -> Result<Option<String>, String> {
+    let tags = tags(text)?;
+
+    for (i, (name, value)) in tags.iter().enumerate() {
+        let valid = match name.as_str() {
+            "v" => i == 0 && value == "DKIM1",
+            "k" => value == "rsa" || value == "ed25519",
+            "p" => value
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=')),
+            _ => true,
+        };
+
+        if !valid {
+            return Err(format!("invalid {}={}", name, value));
+        }
+    }
+
+    match tags.iter().find(|(name, _)| name == "p") {
+        None => Err("the public key (p) is missing".to_owned()),
+        Some((_, key)) if key.is_empty() => Ok(Some("the key is revoked (empty p)".to_owned())),
+        Some(_) => Ok(None),
+    }
+}
+
+/// Check a CAA value: `flags tag "value"` (RFC 8659)
+fn caa(value: &str) -> Result<(), (Severity, String)> {
+    let error = |e: &str| Err((Severity::Error, e.to_owned()));
+
+    let mut parts = value.splitn(3, ' ');
+    let (Some(flags), Some(tag), Some(tag_value)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return error("expected: flags tag \"value\"");
+    };
+
+    if flags.parse::<u8>().is_err() {
+        return error("flags must be a number between 0 and 255");
+    }
+
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return error("tag must be alphanumeric");
+    }
+
+    let Some(tag_value) = tag_value
+        .strip_prefix('"')
+        .and_then(|tag_value| tag_value.strip_suffix('"'))
+    else {
+        return error("value must be quoted");
+    };
+
+    match tag.to_lowercase().as_str() {
+        "issue" | "issuewild" | "contactemail" | "contactphone" => Ok(()),
+        "iodef" => match ["mailto:", "http://", "https://"]
+            .iter()
+            .any(|scheme| tag_value.starts_with(scheme))
+        {
+            true => Ok(()),
+            false => error("iodef must be a mailto: or http(s):// url"),
+        },
+        _ => Err((Severity::Warning, format!("unknown tag {}", tag))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    fn rules(records: &[Record]) -> Vec<(Severity, &'static str, String)> {
+        check("example.org", records, &Zones::new())
+            .into_iter()
+            .map(|finding| (finding.severity, finding.rule, finding.rrset_name))
+            .collect()
+    }
+
+    #[test]
+    fn clean_zone() {
+        let records = [
+            record("@", "A", &["192.0.2.1"]),
+            record("@", "MX", &["10 mail.example.org."]),
+            record("@", "TXT", &["\"v=spf1 mx include:_spf.example.org ~all\""]),
+            record("_spf", "TXT", &["\"v=spf1 ip4:192.0.2.0/24 -all\""]),
+            record("@", "CAA", &["0 issue \"letsencrypt.org\""]),
+            record("mail", "A", &["192.0.2.2"]),
+            record("www", "CNAME", &["@"]),
+            record(
+                "_dmarc",
+                "TXT",
+                &["\"v=DMARC1; p=reject; rua=mailto:dmarc@example.org\""],
+            ),
+            record(
+                "s1._domainkey",
+                "TXT",
+                &["\"v=DKIM1; k=rsa; p=MIGfMA0GCSqGSIb3\""],
+            ),
+        ];
+
+        assert_eq!(rules(&records), vec![]);
+    }
+
+    #[test]
+    fn cname_checks() {
+        let records = [
+            record("@", "CNAME", &["other.example.net."]),
+            record("www", "CNAME", &["@"]),
+            record("www", "TXT", &["\"hello\""]),
+            record("mail", "CNAME", &["@"]),
+            record("@", "MX", &["10 mail"]),
+        ];
+
+        assert_eq!(
+            rules(&records),
+            vec![
+                (Severity::Error, "cname-apex", "@".to_owned()),
+                (Severity::Error, "cname-conflict", "@".to_owned()),
+                (Severity::Error, "target-cname", "@".to_owned()),
+                (Severity::Error, "cname-conflict", "www".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_targets() {
+        let records = [
+            record(
+                "@",
+                "MX",
+                &["10 mx.example.org.", "20 mx.example.net.", "0 ."],
+            ),
+            record("www", "CNAME", &["app"]),
+            record("*.app", "A", &["192.0.2.1"]),
+            record("api", "CNAME", &["v1.app"]),
+        ];
+
+        assert_eq!(
+            rules(&records),
+            vec![
+                (Severity::Warning, "dangling-target", "@".to_owned()),
+                (Severity::Warning, "dangling-target", "www".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn spf_checks() {
+        let records = [
+            record(
+                "@",
+                "TXT",
+                &[
+                    "\"v=spf1 a mx ptr include:a include:b include:c \" \"include:d include:e exists:f a:g redirect=h\"",
+                    "\"v=spf1 -all\"",
+                ],
+            ),
+            record("@", "SPF", &["\"v=spf1 -all\""]),
+        ];
+
+        let unresolved = (Severity::Info, "spf-unresolved-include", "@".to_owned());
+
+        assert_eq!(
+            rules(&records),
+            [
+                vec![
+                    (Severity::Error, "spf-lookups", "@".to_owned()),
+                    (Severity::Error, "spf-multiple", "@".to_owned()),
+                    (Severity::Warning, "spf-type", "@".to_owned()),
+                ],
+                vec![unresolved; 6],
+            ]
+            .concat()
+        );
+
+        let zones = Zones::new();
+        let zone = Zone::new("example.org", &[], &zones);
+        assert_eq!(
+            zone.spf_lookups(
+                "v=spf1 +a -mx ip4:192.0.2.0/24 ~all",
+                &mut vec![],
+                &mut BTreeSet::new()
+            ),
+            2
+        );
+    }
+
+    #[test]
+    fn spf_included_lookups() {
+        let records = [
+            record(
+                "@",
+                "TXT",
+                &["\"v=spf1 include:_spf1.example.org include:_spf2.example.org -all\""],
+            ),
+            record(
+                "_spf1",
+                "TXT",
+                &["\"v=spf1 a mx a:x.example.net a:y.example.net a:z.example.net -all\""],
+            ),
+            record(
+                "_spf2",
+                "TXT",
+                &["\"v=spf1 mx a:x.example.net a:y.example.net include:_spf.example.net -all\""],
+            ),
+            record("loop", "TXT", &["\"v=spf1 include:loop.example.org -all\""]),
+        ];
+
+        // 2 includes, 5 and 4 lookups in the included records
+        assert_eq!(
+            rules(&records),
+            vec![
+                (Severity::Error, "spf-lookups", "@".to_owned()),
+                (Severity::Info, "spf-unresolved-include", "@".to_owned()),
+                (Severity::Info, "spf-unresolved-include", "_spf2".to_owned()),
+            ]
+        );
+
+        let zones = Zones::from([(
+            "example.net".to_owned(),
+            vec![record("_spf", "TXT", &["\"v=spf1 ip4:192.0.2.0/24 -all\""])],
+        )]);
+        let findings: Vec<_> = check("example.org", &records, &zones)
+            .into_iter()
+            .map(|finding| (finding.rule, finding.rrset_name, finding.message))
+            .collect();
+
+        assert_eq!(
+            findings,
+            vec![(
+                "spf-lookups",
+                "@".to_owned(),
+                "11 DNS lookups, the limit is 10".to_owned()
+            )]
+        );
+        assert_eq!(
+            Zone::new("example.org", &records, &zones).spf_unresolved(),
+            BTreeSet::new()
+        );
+    }
+
+    #[test]
+    fn dmarc_dkim_checks() {
+        assert!(dmarc("v=DMARC1; p=quarantine; pct=50").is_ok());
+        assert!(dmarc("p=reject; v=DMARC1").is_err());
+        assert!(dmarc("v=DMARC1; rua=mailto:a@example.org").is_err());
+        assert!(dmarc("v=DMARC1; p=block").is_err());
+        assert!(dmarc("v=DMARC1; p=none; pct=120").is_err());
+        assert!(dmarc("v=DMARC1; p=none; rua=https://example.org").is_err());
+
+        assert_eq!(dkim("k=ed25519; p=abc+/=="), Ok(None));
+        assert!(dkim("p=abc; v=DKIM1").is_err());
+        assert!(dkim("v=DKIM1; k=dsa; p=abc").is_err());
+        assert!(dkim("v=DKIM1; p=not base64!").is_err());
+        assert!(dkim("v=DKIM1; k=rsa").is_err());
+        assert_eq!(
+            dkim("v=DKIM1; p="),
+            Ok(Some("the key is revoked (empty p)".to_owned()))
+        );
+    }
+
+    #[test]
+    fn caa_checks() {
+        assert!(caa("0 issue \"letsencrypt.org\"").is_ok());
+        assert!(caa("128 iodef \"mailto:security@example.org\"").is_ok());
+        assert_eq!(
+            caa("0 iodef \"security@example.org\"").unwrap_err().0,
+            Severity::Error
+        );
+        assert_eq!(caa("256 issue \";\"").unwrap_err().0, Severity::Error);
+        assert_eq!(
+            caa("0 issue letsencrypt.org").unwrap_err().0,
+            Severity::Error
+        );
+        assert_eq!(caa("0 issue").unwrap_err().0, Severity::Error);
+        assert_eq!(caa("0 policy \"x\"").unwrap_err().0, Severity::Warning);
+    }
+
+    #[test]
+    fn inconsistent_ttls() {
+        let mut txt = record("@", "TXT", &["\"hello\""]);
+        txt.rrset_ttl = Some(3600);
+
+        assert_eq!(
+            rules(&[record("@", "A", &["192.0.2.1"]), txt]),
+            vec![(Severity::Info, "ttl-inconsistent", "@".to_owned())]
+        );
+    }
+
+    #[test]
+    fn txt_values() {
+        assert_eq!(txt("\"v=spf1 \" \"-all\""), "v=spf1 -all");
+        assert_eq!(txt("\"say \\\"hi\\\"\""), "say \"hi\"");
+        assert_eq!(txt("plain"), "plain");
+    }
+}
@@ -3,17 +3,47 @@
 
 //! gandictl controls the gandi.net management console.
 
+mod backup;
+mod changes;
 mod cli;
+mod completion;
+mod config;
+mod copy;
+mod ddns;
+mod dry_run;
+#[cfg(test)]
+mod fixtures;
+mod lint;
 mod output;
+mod rename;
+mod search;
+mod selection;
+mod session;
+mod ttl;
+mod watch;
 
 use cli::*;
-use gandi_v5_livedns_api::{records::UpsertRecord, Api, Endpoint};
-use output::handler_yaml;
-use std::{env, error::Error, process::ExitCode};
+use config::{Context, GandiCtlConfig};
+use gandi_v5_livedns_api::{
+    records::{PatchOutcome, PatchRecord, UpsertRecord},
+    TokenSource,
+};
+use output::handler;
+use search::Search;
+use session::Session;
+use std::{error::Error, process::ExitCode};
+
+#[cfg(not(tarpaulin_include))]
+fn main() -> ExitCode {
+    // completers query Gandi Api with their own runtime
+    Cli::complete();
+
+    run()
+}
 
 #[tokio::main]
 #[cfg(not(tarpaulin_include))]
-async fn main() -> ExitCode {
+async fn run() -> ExitCode {
     let terminated = main_delegation().await;
 
     match terminated {
@@ -30,46 +60,212 @@ async fn main() -> ExitCode {
 async fn main_delegation() -> Result<(), Box<dyn Error>> {
     let cli = Cli::init();
 
-    let personal_access_token = env::var("GANDI_V5_PAT")?;
+    match cli.command {
+        ApiCommands::LiveDNS {
+            command:
+                LiveDnsCommands::Lint {
+                    fqdn,
+                    file: Some(file),
+                    fail_on,
+                },
+        } => lint::lint_file(&cli.global, fqdn, &file, fail_on),
+        ApiCommands::LiveDNS { command } => {
+            let session = Session::build(&cli.global)?;
+
+            match command {
+                LiveDnsCommands::Get { command } => livedns_get(command, &session).await,
+                LiveDnsCommands::Apply { command } => livedns_apply(command, &session).await,
+                LiveDnsCommands::Create { command } => livedns_create(command, &session).await,
+                LiveDnsCommands::Patch { command } => livedns_patch(command, &session).await,
+                LiveDnsCommands::Rename {
+                    command:
+                        LiveDnsRenameCommands::Record {
+                            fqdn,
+                            old_name,
+                            new_name,
+                            rrset_type,
+                            cname,
+                        },
+                } => rename::rename(&session, fqdn, old_name, new_name, rrset_type, cname).await,
+                LiveDnsCommands::Delete { command } => livedns_delete(command, &session).await,
+                LiveDnsCommands::Ttl {
+                    command:
+                        LiveDnsTtlCommands::Set {
+                            fqdn,
+                            rrset_ttl,
+                            rrset_name,
+                            rrset_types,
+                            only_above,
+                            state,
+                        },
+                } => {
+                    let selection =
+                        selection::Selection::build(rrset_name.into_iter().collect(), rrset_types)?;
+
+                    ttl::set(&session, fqdn, rrset_ttl, selection, only_above, state).await
+                }
+                LiveDnsCommands::Ttl {
+                    command: LiveDnsTtlCommands::Restore { fqdn, state },
+                } => ttl::restore(&session, fqdn, state).await,
+                LiveDnsCommands::Lint { fqdn, fail_on, .. } => {
+                    lint::lint(&session, fqdn, fail_on).await
+                }
+                LiveDnsCommands::Search {
+                    value,
+                    exact,
+                    rrset_types,
+                    rrset_name,
+                    concurrency,
+                } => {
+                    Search::build(&value, exact, rrset_types, rrset_name.as_deref())?
+                        .run(&session, concurrency.into())
+                        .await
+                }
+                LiveDnsCommands::Copy {
+                    from,
+                    to,
+                    names,
+                    types,
+                    mode,
+                    concurrency,
+                } => {
+                    let selection = selection::Selection::build(names, types)?;
+
+                    copy::copy(&session, from, to, selection, mode, concurrency.into()).await
+                }
+                LiveDnsCommands::Backup { dir, concurrency } => {
+                    backup::backup(&session, &dir, concurrency.into()).await
+                }
+                LiveDnsCommands::Restore {
+                    dir,
+                    fqdns,
+                    prune,
+                    concurrency,
+                } => backup::restore(&session, &dir, fqdns, prune, concurrency.into()).await,
+            }?;
+
+            if session.dry_run {
+                dry_run::report(&session).await?;
+            }
 
-    let endpoint = if cli.sandbox {
-        Endpoint::Sandbox
-    } else {
-        Endpoint::Prod
-    };
+            Ok(())
+        }
+        ApiCommands::Ddns(args) => {
+            let session = Session::build(&cli.global)?;
 
-    let api = Api::build(endpoint, &personal_access_token)?;
+            ddns::ddns(args, &session).await?;
 
-    match cli.command {
-        ApiCommands::LiveDNS { command } => match command {
-            LiveDnsCommands::Get { command } => livedns_get(command, &api).await,
-            LiveDnsCommands::Apply { command } => livedns_apply(command, &api).await,
-            LiveDnsCommands::Create { command } => livedns_create(command, &api).await,
-            LiveDnsCommands::Delete { command } => livedns_delete(command, &api).await,
-        },
+            if session.dry_run {
+                dry_run::report(&session).await?;
+            }
+
+            Ok(())
+        }
+        ApiCommands::Config { command } => config(command, &cli.global),
+        ApiCommands::Completion { shell } => completion::completion(shell),
+        ApiCommands::Man { directory } => completion::man(&directory),
+    }
+}
+
+fn config(command: ConfigCommands, options: &GlobalOptions) -> Result<(), Box<dyn Error>> {
+    let path = GandiCtlConfig::path()?;
+    let mut config = GandiCtlConfig::load(&path)?;
+
+    match command {
+        ConfigCommands::UseContext { name } => {
+            config.use_context(&name)?;
+            config.save(&path)?;
+
+            println!("Switched to context {} !", name);
+        }
+        ConfigCommands::GetContexts {} => handler(config.entries(), &options.output)?,
+        ConfigCommands::SetContext {
+            name,
+            endpoint,
+            token_env,
+            token_file,
+            token_command,
+            token_keyring,
+            sharing_id,
+            domain,
+        } => {
+            let token = token_env
+                .map(TokenSource::Env)
+                .or(token_file.map(TokenSource::File))
+                .or(token_command.map(TokenSource::Command))
+                .or(token_keyring.map(|keyring| TokenSource::Keyring {
+                    service: keyring[0].to_owned(),
+                    user: keyring[1].to_owned(),
+                }));
+
+            config.set_context(
+                &name,
+                Context {
+                    endpoint,
+                    token,
+                    sharing_id,
+                    domain,
+                },
+            );
+            config.save(&path)?;
+
+            println!("Context {} set !", name);
+        }
     }
+
+    Ok(())
 }
 
-async fn livedns_get(command: LiveDnsGetCommands, api: &Api) -> Result<(), Box<dyn Error>> {
+async fn livedns_get(command: LiveDnsGetCommands, session: &Session) -> Result<(), Box<dyn Error>> {
+    let (api, output) = (&session.api, &session.output);
+
     match command {
-        LiveDnsGetCommands::Domains {} => handler_yaml(api.domains().await?),
-        LiveDnsGetCommands::Domain { fqdn } => handler_yaml(api.domain(&fqdn).await?),
-        LiveDnsGetCommands::Records { fqdn, rrset_name } => match rrset_name {
-            Some(rrset_name) => handler_yaml(api.records_by_name(&fqdn, &rrset_name).await?),
-            None => handler_yaml(api.records(&fqdn).await?),
-        },
+        LiveDnsGetCommands::Domains {} => handler(api.domains().await?, output),
+        LiveDnsGetCommands::Domain { fqdn } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            handler(api.domain(&fqdn).await?, output)
+        }
+        LiveDnsGetCommands::Records {
+            fqdn,
+            rrset_name,
+            watch: true,
+            interval,
+        } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            watch::watch(session, &fqdn, rrset_name.as_deref(), interval).await
+        }
+        LiveDnsGetCommands::Records {
+            fqdn, rrset_name, ..
+        } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            match rrset_name {
+                Some(rrset_name) => handler(api.records_by_name(&fqdn, &rrset_name).await?, output),
+                None => handler(api.records(&fqdn).await?, output),
+            }
+        }
         LiveDnsGetCommands::Record {
             fqdn,
             rrset_name,
             rrset_type,
-        } => handler_yaml(
-            api.record_by_name_and_type(&fqdn, &rrset_name, &rrset_type)
-                .await?,
-        ),
+        } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            handler(
+                api.record_by_name_and_type(&fqdn, &rrset_name, &rrset_type)
+                    .await?,
+                output,
+            )
+        }
     }
 }
 
-async fn livedns_apply(command: LiveDnsApplyCommands, api: &Api) -> Result<(), Box<dyn Error>> {
+async fn livedns_apply(
+    command: LiveDnsApplyCommands,
+    session: &Session,
+) -> Result<(), Box<dyn Error>> {
     match command {
         LiveDnsApplyCommands::Record {
             fqdn,
@@ -78,25 +274,36 @@ async fn livedns_apply(command: LiveDnsApplyCommands, api: &Api) -> Result<(), B
             rrset_values,
             rrset_ttl,
         } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            session
+                .confirm("Overwrite", &fqdn, &rrset_name, &rrset_type)
+                .await?;
+
             let record = UpsertRecord {
                 rrset_values,
                 rrset_ttl,
             };
 
-            api.upsert_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type, &record)
+            session
+                .api
+                .upsert_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type, &record)
                 .await?;
 
-            println!(
+            session.notify(&format!(
                 "Record {}.{} type {} applied !",
                 rrset_name, fqdn, rrset_type
-            );
+            ));
 
             Ok(())
         }
     }
 }
 
-async fn livedns_create(command: LiveDnsCreateCommands, api: &Api) -> Result<(), Box<dyn Error>> {
+async fn livedns_create(
+    command: LiveDnsCreateCommands,
+    session: &Session,
+) -> Result<(), Box<dyn Error>> {
     match command {
         cli::LiveDnsCreateCommands::Record {
             fqdn,
@@ -105,38 +312,105 @@ async fn livedns_create(command: LiveDnsCreateCommands, api: &Api) -> Result<(),
             rrset_values,
             rrset_ttl,
         } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            session.protect(&fqdn, &rrset_name, &rrset_type)?;
+
             let record = UpsertRecord {
                 rrset_values,
                 rrset_ttl,
             };
 
-            api.create_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type, &record)
+            session
+                .api
+                .create_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type, &record)
                 .await?;
 
-            println!(
+            session.notify(&format!(
                 "Record {}.{} type {} created !",
                 rrset_name, fqdn, rrset_type
-            );
+            ));
+
+            Ok(())
+        }
+    }
+}
+
+async fn livedns_patch(
+    command: LiveDnsPatchCommands,
+    session: &Session,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        LiveDnsPatchCommands::Record {
+            fqdn,
+            rrset_name,
+            rrset_type,
+            add_value,
+            remove_value,
+            rrset_ttl,
+        } => {
+            let fqdn = session.fqdn(fqdn)?;
+
+            if remove_value.is_empty() {
+                session.protect(&fqdn, &rrset_name, &rrset_type)?;
+            } else {
+                session
+                    .confirm("Remove values of", &fqdn, &rrset_name, &rrset_type)
+                    .await?;
+            }
+
+            let patch = PatchRecord {
+                add_values: add_value,
+                remove_values: remove_value,
+                rrset_ttl,
+            };
+
+            let outcome = session
+                .api
+                .patch_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type, &patch)
+                .await?;
+
+            let action = match outcome {
+                PatchOutcome::Unchanged => "unchanged",
+                PatchOutcome::Upserted(_) => "patched",
+                PatchOutcome::Deleted => "deleted (no value left)",
+            };
+
+            session.notify(&format!(
+                "Record {}.{} type {} {} !",
+                rrset_name, fqdn, rrset_type, action
+            ));
 
             Ok(())
         }
     }
 }
 
-async fn livedns_delete(command: LiveDnsDeleteCommands, api: &Api) -> Result<(), Box<dyn Error>> {
+async fn livedns_delete(
+    command: LiveDnsDeleteCommands,
+    session: &Session,
+) -> Result<(), Box<dyn Error>> {
     match command {
         cli::LiveDnsDeleteCommands::Record {
             fqdn,
             rrset_name,
             rrset_type,
         } => {
-            api.delete_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type)
+            let fqdn = session.fqdn(fqdn)?;
+
+            session
+                .confirm("Delete", &fqdn, &rrset_name, &rrset_type)
                 .await?;
 
-            println!(
+            session
+                .api
+                .delete_record_by_name_and_type(&fqdn, &rrset_name, &rrset_type)
+                .await?;
+
+            session.notify(&format!(
                 "Record {}.{} type {} deleted !",
                 rrset_name, fqdn, rrset_type
-            );
+            ));
 
             Ok(())
         }
@@ -149,29 +423,45 @@ mod tests {
     use crate::livedns_create;
     use crate::livedns_delete;
     use crate::livedns_get;
-    use crate::Api;
+    use crate::output::Output;
+    use crate::Session;
+    use gandi_v5_livedns_api::{Api, Endpoint};
     use std::env;
 
+    fn sandbox_session() -> Session {
+        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
+        let api = Api::build(Endpoint::Sandbox, &pat).unwrap();
+
+        Session {
+            api,
+            endpoint: "sandbox".to_owned(),
+            output: Output::Yaml,
+            domain: None,
+            dry_run: false,
+            yes: true,
+            force: false,
+            protected: vec![],
+        }
+    }
+
     #[tokio::test]
     async fn get_domains() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
-        let res = livedns_get(crate::LiveDnsGetCommands::Domains {}, &api).await;
+        let res = livedns_get(crate::LiveDnsGetCommands::Domains {}, &session).await;
 
         assert!(res.is_ok());
     }
 
     #[tokio::test]
     async fn get_domain_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_get(
             crate::LiveDnsGetCommands::Domain {
                 fqdn: "pygoscelis-sandbox.org".to_owned(),
             },
-            &api,
+            &session,
         )
         .await;
 
@@ -181,15 +471,16 @@ mod tests {
 
     #[tokio::test]
     async fn get_records_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_get(
             crate::LiveDnsGetCommands::Records {
                 fqdn: "pygoscelis-sandbox.org".to_owned(),
                 rrset_name: None,
+                watch: false,
+                interval: std::time::Duration::from_secs(30),
             },
-            &api,
+            &session,
         )
         .await;
 
@@ -199,15 +490,16 @@ mod tests {
 
     #[tokio::test]
     async fn get_records_name_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_get(
             crate::LiveDnsGetCommands::Records {
                 fqdn: "pygoscelis-sandbox.org".to_owned(),
                 rrset_name: Some("test".to_owned()),
+                watch: false,
+                interval: std::time::Duration::from_secs(30),
             },
-            &api,
+            &session,
         )
         .await;
 
@@ -217,8 +509,7 @@ mod tests {
 
     #[tokio::test]
     async fn get_record_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_get(
             crate::LiveDnsGetCommands::Record {
@@ -226,7 +517,7 @@ mod tests {
                 rrset_name: "test".to_owned(),
                 rrset_type: "A".to_owned(),
             },
-            &api,
+            &session,
         )
         .await;
 
@@ -236,8 +527,7 @@ mod tests {
 
     #[tokio::test]
     async fn create_record_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_create(
             crate::LiveDnsCreateCommands::Record {
@@ -247,7 +537,7 @@ mod tests {
                 rrset_values: vec!["127.0.0.1".to_owned()],
                 rrset_ttl: None,
             },
-            &api,
+            &session,
         )
         .await;
 
@@ -257,8 +547,7 @@ mod tests {
 
     #[tokio::test]
     async fn apply_record_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_apply(
             crate::LiveDnsApplyCommands::Record {
@@ -268,7 +557,7 @@ mod tests {
                 rrset_values: vec!["127.0.0.1".to_owned()],
                 rrset_ttl: None,
             },
-            &api,
+            &session,
         )
         .await;
 
@@ -278,8 +567,7 @@ mod tests {
 
     #[tokio::test]
     async fn delete_record_404() {
-        let pat = env::var("GANDI_V5_SANDBOX_PAT").unwrap();
-        let api = Api::build(crate::Endpoint::Sandbox, &pat).unwrap();
+        let session = sandbox_session();
 
         let res = livedns_delete(
             crate::LiveDnsDeleteCommands::Record {
@@ -287,7 +575,7 @@ mod tests {
                 rrset_name: "test".to_owned(),
                 rrset_type: "A".to_owned(),
             },
-            &api,
+            &session,
         )
         .await;
 
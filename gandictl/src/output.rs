@@ -1,14 +1,434 @@
+use gandi_v5_livedns_api::{
+    domains::{Domain, DomainInfo},
+    records::Record,
+    search::RecordMatch,
+};
 use serde::Serialize;
-use std::error::Error;
+use std::{error::Error, str::FromStr};
 
-pub(crate) fn handler_yaml<T>(data: T) -> Result<(), Box<dyn Error>>
-// only handle serde_yaml::Error but simplifying caller code by using dyn Error
+/// Max length of the values column with the table output
+const TABLE_VALUES_MAX_LEN: usize = 64;
+
+/// Values column of a row, truncated unless the output is wide
+fn values_column(mut values: String, wide: bool) -> String {
+    if !wide && values.chars().count() > TABLE_VALUES_MAX_LEN {
+        values = values.chars().take(TABLE_VALUES_MAX_LEN - 3).collect();
+        values.push_str("...");
+    }
+
+    values
+}
+
+/// Supported output formats
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Output {
+    Yaml,
+    Json,
+    Table,
+    Wide,
+    Name,
+    Zone,
+    Template(String),
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yaml" => Ok(Output::Yaml),
+            "json" => Ok(Output::Json),
+            "table" => Ok(Output::Table),
+            "wide" => Ok(Output::Wide),
+            "name" => Ok(Output::Name),
+            "zone" => Ok(Output::Zone),
+            _ => match s.strip_prefix("template=") {
+                Some(template) if !template.is_empty() => Ok(Output::Template(template.to_owned())),
+                _ => Err(format!(
+                    "'{s}' isn't one of yaml, json, table, wide, name, zone or template=..."
+                )),
+            },
+        }
+    }
+}
+
+/// A resource that can be displayed with any [`Output`]
+pub(crate) trait Resource: Serialize {
+    /// Header of the table (or wide) output
+    fn headers(wide: bool) -> Vec<&'static str>;
+    /// Row of the table (or wide) output
+    fn row(&self, wide: bool) -> Vec<String>;
+    /// Identifier of the resource
+    fn name(&self) -> String;
+    /// Lines of a BIND zone file
+    fn zone(&self) -> Result<Vec<String>, String> {
+        Err("zone output is only supported for records".to_owned())
+    }
+}
+
+/// One or many resources to display
+pub(crate) trait Printable: Serialize {
+    type Item: Resource;
+
+    fn items(&self) -> &[Self::Item];
+}
+
+impl<T: Resource> Printable for T {
+    type Item = T;
+
+    fn items(&self) -> &[T] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl<T: Resource> Printable for Vec<T> {
+    type Item = T;
+
+    fn items(&self) -> &[T] {
+        self
+    }
+}
+
+impl Resource for Domain {
+    fn headers(_wide: bool) -> Vec<&'static str> {
+        vec!["FQDN"]
+    }
+
+    fn row(&self, _wide: bool) -> Vec<String> {
+        vec![self.fqdn.to_owned()]
+    }
+
+    fn name(&self) -> String {
+        self.fqdn.to_owned()
+    }
+}
+
+impl Resource for DomainInfo {
+    fn headers(_wide: bool) -> Vec<&'static str> {
+        vec!["FQDN", "AUTOMATIC_SNAPSHOT"]
+    }
+
+    fn row(&self, _wide: bool) -> Vec<String> {
+        vec![
+            self.fqdn.to_owned(),
+            self.automatic_snapshot
+                .map(|snapshot| snapshot.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+
+    fn name(&self) -> String {
+        self.fqdn.to_owned()
+    }
+}
+
+impl Resource for Record {
+    fn headers(_wide: bool) -> Vec<&'static str> {
+        vec!["NAME", "TYPE", "TTL", "VALUES"]
+    }
+
+    fn row(&self, wide: bool) -> Vec<String> {
+        vec![
+            self.rrset_name.to_owned(),
+            self.rrset_type.to_owned(),
+            self.rrset_ttl
+                .map(|ttl| ttl.to_string())
+                .unwrap_or_default(),
+            values_column(self.rrset_values.join(","), wide),
+        ]
+    }
+
+    fn name(&self) -> String {
+        format!("{}/{}", self.rrset_name, self.rrset_type)
+    }
+
+    fn zone(&self) -> Result<Vec<String>, String> {
+        let ttl = self
+            .rrset_ttl
+            .map(|ttl| format!("{ttl}\t"))
+            .unwrap_or_default();
+
+        Ok(self
+            .rrset_values
+            .iter()
+            .map(|value| {
+                format!(
+                    "{}\t{}IN\t{}\t{}",
+                    self.rrset_name, ttl, self.rrset_type, value
+                )
+            })
+            .collect())
+    }
+}
+
+impl Resource for RecordMatch {
+    fn headers(_wide: bool) -> Vec<&'static str> {
+        vec!["FQDN", "NAME", "TYPE", "TTL", "VALUE"]
+    }
+
+    fn row(&self, wide: bool) -> Vec<String> {
+        vec![
+            self.fqdn.to_owned(),
+            self.rrset_name.to_owned(),
+            self.rrset_type.to_owned(),
+            self.rrset_ttl
+                .map(|ttl| ttl.to_string())
+                .unwrap_or_default(),
+            values_column(self.rrset_value.to_owned(), wide),
+        ]
+    }
+
+    fn name(&self) -> String {
+        format!("{}/{}/{}", self.fqdn, self.rrset_name, self.rrset_type)
+    }
+
+    fn zone(&self) -> Result<Vec<String>, String> {
+        let owner = match self.rrset_name.as_str() {
+            "@" => format!("{}.", self.fqdn),
+            name => format!("{}.{}.", name, self.fqdn),
+        };
+        let ttl = self
+            .rrset_ttl
+            .map(|ttl| format!("{ttl}\t"))
+            .unwrap_or_default();
+
+        Ok(vec![format!(
+            "{}\t{}IN\t{}\t{}",
+            owner, ttl, self.rrset_type, self.rrset_value
+        )])
+    }
+}
+
+/// Print data with the output format requested
+pub(crate) fn handler<T>(data: T, output: &Output) -> Result<(), Box<dyn Error>>
 where
-    T: Serialize,
+    T: Printable,
 {
-    let yaml = serde_yaml::to_string(&data)?;
+    let rendered = render(&data, output)?;
 
-    println!("{}", yaml);
+    if !rendered.is_empty() {
+        println!("{}", rendered);
+    }
 
     Ok(())
 }
+
+pub(crate) fn render<T>(data: &T, output: &Output) -> Result<String, Box<dyn Error>>
+where
+    T: Printable,
+{
+    Ok(match output {
+        Output::Yaml => serde_yaml::to_string(data)?,
+        Output::Json => serde_json::to_string_pretty(data)?,
+        Output::Table => table(data.items(), false),
+        Output::Wide => table(data.items(), true),
+        Output::Name => data
+            .items()
+            .iter()
+            .map(Resource::name)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Output::Zone => {
+            let mut lines = vec![];
+
+            for item in data.items() {
+                lines.extend(item.zone()?);
+            }
+
+            lines.join("\n")
+        }
+        Output::Template(template) => data
+            .items()
+            .iter()
+            .map(|item| render_template(template, item))
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n"),
+    })
+}
+
+fn table<T: Resource>(items: &[T], wide: bool) -> String {
+    let headers: Vec<String> = T::headers(wide).into_iter().map(String::from).collect();
+    let rows: Vec<Vec<String>> = items.iter().map(|item| item.row(wide)).collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    std::iter::once(&headers)
+        .chain(rows.iter())
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replace every `{{field}}` of the template with the value of the field.
+///
+/// Lists are joined with a space.
+fn render_template<T: Serialize>(template: &str, item: &T) -> Result<String, Box<dyn Error>> {
+    let value = serde_json::to_value(item)?;
+
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or(format!("template: unclosed '{{{{' in '{}'", template))?;
+
+        rendered.push_str(&rest[..start]);
+
+        let field = rest[start + 2..start + end].trim();
+        let field_value = value
+            .get(field)
+            .ok_or(format!("template: unknown field '{}'", field))?;
+
+        rendered.push_str(&template_value(field_value));
+
+        rest = &rest[start + end + 2..];
+    }
+
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+fn template_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(value) => value.to_owned(),
+        serde_json::Value::Array(values) => values
+            .iter()
+            .map(template_value)
+            .collect::<Vec<_>>()
+            .join(" "),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record {
+                rrset_name: "@".to_owned(),
+                rrset_type: "MX".to_owned(),
+                rrset_values: vec![
+                    "10 spool.mail.gandi.net.".to_owned(),
+                    "50 fb.mail.gandi.net.".to_owned(),
+                ],
+                rrset_ttl: Some(10800),
+            },
+            Record {
+                rrset_name: "www".to_owned(),
+                rrset_type: "CNAME".to_owned(),
+                rrset_values: vec!["webredir.vip.gandi.net.".to_owned()],
+                rrset_ttl: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn output_from_str() {
+        assert_eq!("yaml".parse::<Output>(), Ok(Output::Yaml));
+        assert_eq!("wide".parse::<Output>(), Ok(Output::Wide));
+        assert_eq!(
+            "template={{rrset_name}}".parse::<Output>(),
+            Ok(Output::Template("{{rrset_name}}".to_owned()))
+        );
+        assert!("template=".parse::<Output>().is_err());
+        assert!("xml".parse::<Output>().is_err());
+    }
+
+    #[test]
+    fn render_table() {
+        let res = render(&records(), &Output::Table).unwrap();
+
+        assert_eq!(
+            res,
+            "NAME  TYPE   TTL    VALUES\n\
+             @     MX     10800  10 spool.mail.gandi.net.,50 fb.mail.gandi.net.\n\
+             www   CNAME         webredir.vip.gandi.net."
+        );
+    }
+
+    #[test]
+    fn render_table_truncates_values() {
+        let record = Record {
+            rrset_name: "@".to_owned(),
+            rrset_type: "TXT".to_owned(),
+            rrset_values: vec!["x".repeat(100)],
+            rrset_ttl: Some(300),
+        };
+
+        let table = render(&record, &Output::Table).unwrap();
+        let wide = render(&record, &Output::Wide).unwrap();
+
+        assert!(table.ends_with(&format!("{}...", "x".repeat(TABLE_VALUES_MAX_LEN - 3))));
+        assert!(wide.ends_with(&"x".repeat(100)));
+    }
+
+    #[test]
+    fn render_name() {
+        let res = render(&records(), &Output::Name).unwrap();
+
+        assert_eq!(res, "@/MX\nwww/CNAME");
+    }
+
+    #[test]
+    fn render_zone() {
+        let res = render(&records(), &Output::Zone).unwrap();
+
+        assert_eq!(
+            res,
+            "@\t10800\tIN\tMX\t10 spool.mail.gandi.net.\n\
+             @\t10800\tIN\tMX\t50 fb.mail.gandi.net.\n\
+             www\tIN\tCNAME\twebredir.vip.gandi.net."
+        );
+    }
+
+    #[test]
+    fn render_zone_unsupported() {
+        let domain = Domain {
+            fqdn: "example.org".to_owned(),
+        };
+
+        assert!(render(&domain, &Output::Zone).is_err());
+    }
+
+    #[test]
+    fn render_template_fields() {
+        let output = Output::Template("{{rrset_name}} {{ rrset_values }} {{rrset_ttl}}".to_owned());
+
+        let res = render(&records(), &output).unwrap();
+
+        assert_eq!(
+            res,
+            "@ 10 spool.mail.gandi.net. 50 fb.mail.gandi.net. 10800\nwww webredir.vip.gandi.net. "
+        );
+    }
+
+    #[test]
+    fn render_template_unknown_field() {
+        let output = Output::Template("{{fqdn}}".to_owned());
+
+        let res = render(&records(), &output);
+
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "template: unknown field 'fqdn'"
+        );
+    }
+}
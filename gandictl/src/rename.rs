@@ -0,0 +1,215 @@
+use gandi_v5_livedns_api::records::Record;
+use std::error::Error;
+
+use crate::{changes::Change, session::Session};
+
+/// Move the records of a name (all types by default) to a new name of the same domain
+///
+/// With `cname`, a CNAME to the new name is left behind. Applied changes are rolled back when a change fails.
+pub(crate) async fn rename(
+    session: &Session,
+    fqdn: String,
+    old_name: String,
+    new_name: String,
+    rrset_type: Option<String>,
+    cname: bool,
+) -> Result<(), Box<dyn Error>> {
+    let fqdn = session.fqdn(fqdn)?;
+    let rrset_type = rrset_type.map(|rrset_type| rrset_type.to_uppercase());
+    let records = session.api.records(&fqdn).await?;
+
+    let changes = plan(&records, &old_name, &new_name, rrset_type.as_deref(), cname)?;
+
+    println!("{}:", fqdn);
+
+    for change in &changes {
+        let record = change.record();
+        session.protect(&fqdn, &record.rrset_name, &record.rrset_type)?;
+
+        print!("{}", change);
+    }
+
+    if !session.yes && !session.dry_run {
+        session.ask(
+            &format!("Rename {} to {} on {}? [y/N] ", old_name, new_name, fqdn),
+            "Rename",
+        )?;
+    }
+
+    let mut applied: Vec<&Change> = vec![];
+
+    for change in &changes {
+        if let Err(e) = change.apply(session, &fqdn).await {
+            let mut errors = vec![format!("rename: {} ({})", e, fqdn)];
+
+            for change in applied.iter().rev() {
+                if let Err(e) = change.revert().apply(session, &fqdn).await {
+                    let record = change.record();
+                    errors.push(format!(
+                        "rename: rollback of {} {} failed: {}",
+                        record.rrset_name, record.rrset_type, e
+                    ));
+                }
+            }
+
+            return Err(errors.join("\n").into());
+        }
+
+        applied.push(change);
+    }
+
+    session.notify(&format!(
+        "Records {} renamed to {} on {} !",
+        old_name, new_name, fqdn
+    ));
+
+    Ok(())
+}
+
+/// Changes creating the records with the new name before deleting the old ones
+fn plan(
+    records: &[Record],
+    old_name: &str,
+    new_name: &str,
+    rrset_type: Option<&str>,
+    cname: bool,
+) -> Result<Vec<Change>, Box<dyn Error>> {
+    if old_name == new_name {
+        return Err(format!("rename: {} is already the name", new_name).into());
+    }
+
+    let (moved, kept): (Vec<&Record>, Vec<&Record>) = records
+        .iter()
+        .filter(|record| record.rrset_name == old_name)
+        .partition(|record| rrset_type.is_none_or(|rrset_type| record.rrset_type == rrset_type));
+
+    if moved.is_empty() {
+        return Err(format!(
+            "rename: no record {} {}",
+            old_name,
+            rrset_type.unwrap_or_default()
+        )
+        .trim_end()
+        .to_owned()
+        .into());
+    }
+
+    for record in &moved {
+        if records
+            .iter()
+            .any(|r| r.rrset_name == new_name && r.rrset_type == record.rrset_type)
+        {
+            return Err(
+                format!("rename: {} {} already exists", new_name, record.rrset_type).into(),
+            );
+        }
+    }
+
+    if cname && !kept.is_empty() {
+        return Err(format!(
+            "rename: can't leave a CNAME, {} keeps {} records",
+            old_name,
+            kept.iter()
+                .map(|record| record.rrset_type.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into());
+    }
+
+    let mut changes: Vec<Change> = moved
+        .iter()
+        .map(|record| {
+            Change::Create(Record {
+                rrset_name: new_name.to_owned(),
+                ..(*record).to_owned()
+            })
+        })
+        .collect();
+
+    changes.extend(
+        moved
+            .iter()
+            .map(|record| Change::Delete((*record).to_owned())),
+    );
+
+    if cname {
+        changes.push(Change::Create(Record {
+            rrset_name: old_name.to_owned(),
+            rrset_type: "CNAME".to_owned(),
+            rrset_values: vec![new_name.to_owned()],
+            rrset_ttl: None,
+        }));
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    fn records() -> Vec<Record> {
+        vec![
+            record("www", "A", &["10.0.0.1"]),
+            record("www", "AAAA", &["2001:db8::1"]),
+            record("mail", "A", &["10.0.0.2"]),
+        ]
+    }
+
+    #[test]
+    fn plan_all_types() {
+        let changes = plan(&records(), "www", "web", None, true).unwrap();
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Create(record("web", "A", &["10.0.0.1"])),
+                Change::Create(record("web", "AAAA", &["2001:db8::1"])),
+                Change::Delete(record("www", "A", &["10.0.0.1"])),
+                Change::Delete(record("www", "AAAA", &["2001:db8::1"])),
+                Change::Create(Record {
+                    rrset_ttl: None,
+                    ..record("www", "CNAME", &["web"])
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_single_type() {
+        let changes = plan(&records(), "www", "web", Some("AAAA"), false).unwrap();
+
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!(
+            plan(&records(), "www", "web", Some("AAAA"), true)
+                .unwrap_err()
+                .to_string(),
+            "rename: can't leave a CNAME, www keeps A records"
+        );
+    }
+
+    #[test]
+    fn plan_errors() {
+        assert_eq!(
+            plan(&records(), "www", "mail", None, false)
+                .unwrap_err()
+                .to_string(),
+            "rename: mail A already exists"
+        );
+        assert_eq!(
+            plan(&records(), "ftp", "web", None, false)
+                .unwrap_err()
+                .to_string(),
+            "rename: no record ftp"
+        );
+        assert_eq!(
+            plan(&records(), "www", "www", None, false)
+                .unwrap_err()
+                .to_string(),
+            "rename: www is already the name"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use gandi_v5_livedns_api::records::Record;
+use glob::Pattern;
+use regex::{Regex, RegexBuilder};
+use std::error::Error;
+
+use crate::{output::handler, session::Session};
+
+/// Values matched by a search
+enum Value {
+    /// Regex matching values (case insensitive)
+    Regex(Regex),
+    /// A whole hostname or address of the value
+    Exact(String),
+}
+
+/// Values of records to find across domains
+pub(crate) struct Search {
+    value: Value,
+    rrset_types: Vec<String>,
+    /// Glob pattern of the name of records
+    rrset_name: Option<Pattern>,
+}
+
+impl Search {
+    pub(crate) fn build(
+        value: &str,
+        exact: bool,
+        rrset_types: Vec<String>,
+        rrset_name: Option<&str>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let value = match exact {
+            true => Value::Exact(normalize(value)),
+            false => Value::Regex(
+                RegexBuilder::new(value)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("search: invalid regex {}", e))?,
+            ),
+        };
+
+        Ok(Search {
+            value,
+            rrset_types: rrset_types
+                .iter()
+                .map(|rrset_type| rrset_type.to_uppercase())
+                .collect(),
+            rrset_name: rrset_name
+                .map(|name| Pattern::new(&name.to_lowercase()))
+                .transpose()
+                .map_err(|e| format!("search: invalid name pattern {}", e))?,
+        })
+    }
+
+    /// Returns true if the value of the record is searched
+    pub(crate) fn matches(&self, record: &Record, value: &str) -> bool {
+        if !self.rrset_types.is_empty() && !self.rrset_types.contains(&record.rrset_type) {
+            return false;
+        }
+
+        if let Some(pattern) = &self.rrset_name {
+            if !pattern.matches(&record.rrset_name.to_lowercase()) {
+                return false;
+            }
+        }
+
+        match &self.value {
+            // any word of the value (eg: the target of a MX)
+            Value::Exact(exact) => value
+                .split_whitespace()
+                .any(|word| normalize(word) == *exact),
+            Value::Regex(regex) => regex.is_match(value),
+        }
+    }
+
+    /// Search across every domain and print matching values
+    pub(crate) async fn run(
+        &self,
+        session: &Session,
+        concurrency: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        let matches = session
+            .api
+            .search_records(concurrency, |_, record, value| self.matches(record, value))
+            .await?;
+
+        handler(matches, &session.output)
+    }
+}
+
+/// Lowercase without the trailing dot of absolute names
+fn normalize(value: &str) -> String {
+    value.trim_end_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    #[test]
+    fn search_regex() {
+        let search = Search::build(r"^192\.0\.2\.", false, vec![], None).unwrap();
+
+        assert!(search.matches(&record("www", "A", &[]), "192.0.2.10"));
+        assert!(!search.matches(&record("www", "A", &[]), "10.192.0.2"));
+
+        let search = Search::build("MAIL", false, vec!["mx".to_owned()], None).unwrap();
+
+        assert!(search.matches(&record("@", "MX", &[]), "10 mail.example.org."));
+        assert!(!search.matches(&record("mail", "CNAME", &[]), "mail.example.net."));
+    }
+
+    #[test]
+    fn search_exact() {
+        let search = Search::build("Mail.example.org", true, vec![], Some("@")).unwrap();
+
+        assert!(search.matches(&record("@", "MX", &[]), "10 mail.example.org."));
+        assert!(!search.matches(&record("@", "MX", &[]), "10 mail2.example.org."));
+        assert!(!search.matches(&record("www", "CNAME", &[]), "mail.example.org."));
+
+        let search = Search::build("192.0.2.1", true, vec![], Some("w*")).unwrap();
+
+        assert!(search.matches(&record("web", "A", &[]), "192.0.2.1"));
+        assert!(!search.matches(&record("web", "A", &[]), "192.0.2.10"));
+
+        // not a regex
+        let search = Search::build("*.example.org", true, vec![], None).unwrap();
+
+        assert!(search.matches(&record("*", "CNAME", &[]), "*.example.org."));
+        assert!(Search::build("*.example.org", false, vec![], None).is_err());
+    }
+
+    #[test]
+    fn search_invalid() {
+        assert!(Search::build("(", false, vec![], None).is_err());
+        assert!(Search::build("x", false, vec![], Some("[")).is_err());
+    }
+}
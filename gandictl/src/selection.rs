@@ -0,0 +1,76 @@
+use gandi_v5_livedns_api::records::Record;
+use glob::Pattern;
+use std::error::Error;
+
+/// Records selected by names and types
+pub(crate) struct Selection {
+    /// Glob patterns of the names of records (all names when empty)
+    rrset_names: Vec<Pattern>,
+    /// Types of records (all types when empty)
+    rrset_types: Vec<String>,
+}
+
+impl Selection {
+    pub(crate) fn build(
+        rrset_names: Vec<String>,
+        rrset_types: Vec<String>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Selection {
+            rrset_names: rrset_names
+                .iter()
+                .map(|name| Pattern::new(&name.to_lowercase()))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("invalid name pattern {}", e))?,
+            rrset_types: rrset_types
+                .iter()
+                .map(|rrset_type| rrset_type.to_uppercase())
+                .collect(),
+        })
+    }
+
+    /// Returns true if the record is selected
+    fn matches(&self, record: &Record) -> bool {
+        let name = record.rrset_name.to_lowercase();
+
+        (self.rrset_names.is_empty() || self.rrset_names.iter().any(|p| p.matches(&name)))
+            && (self.rrset_types.is_empty() || self.rrset_types.contains(&record.rrset_type))
+    }
+
+    /// Returns true if the type is explicitly selected
+    pub(crate) fn has_type(&self, rrset_type: &str) -> bool {
+        self.rrset_types
+            .iter()
+            .any(|selected| selected == rrset_type)
+    }
+
+    /// Returns the selected records
+    pub(crate) fn filter(&self, records: Vec<Record>) -> Vec<Record> {
+        records
+            .into_iter()
+            .filter(|record| self.matches(record))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    #[test]
+    fn selection() {
+        let selection = Selection::build(
+            vec!["www".to_owned(), "_dmarc*".to_owned()],
+            vec!["cname".to_owned(), "TXT".to_owned()],
+        )
+        .unwrap();
+
+        assert!(selection.matches(&record("www", "CNAME", &["@"])));
+        assert!(selection.matches(&record("_DMARC", "TXT", &["\"v=DMARC1\""])));
+        assert!(!selection.matches(&record("www", "A", &["10.0.0.1"])));
+        assert!(!selection.matches(&record("mail", "TXT", &["\"v=spf1 -all\""])));
+
+        let all = Selection::build(vec![], vec![]).unwrap();
+        assert!(all.matches(&record("mail", "MX", &["10 mail"])));
+    }
+}
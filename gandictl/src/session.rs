@@ -0,0 +1,308 @@
+use gandi_v5_livedns_api::{Api, Endpoint, TokenSource};
+use std::{
+    error::Error,
+    io::{self, BufRead, IsTerminal, Write},
+};
+
+use crate::{
+    cli::GlobalOptions,
+    config::{
+        endpoint_name, parse_endpoint, Context, GandiCtlConfig, ProtectedRecord, DEFAULT_TOKEN_ENV,
+    },
+    output::Output,
+};
+
+/// Api and defaults resolved from the command line and the selected context
+pub(crate) struct Session {
+    pub(crate) api: Api,
+    /// Name of the endpoint in the caches (see [`endpoint_name`])
+    pub(crate) endpoint: String,
+    pub(crate) output: Output,
+    /// Default domain of the context
+    pub(crate) domain: Option<String>,
+    /// Requests modifying resources are only recorded
+    pub(crate) dry_run: bool,
+    /// Don't ask for confirmation
+    pub(crate) yes: bool,
+    /// Modify protected records
+    pub(crate) force: bool,
+    /// Records that can't be modified without `--force`
+    pub(crate) protected: Vec<ProtectedRecord>,
+}
+
+impl Session {
+    /// Returns a new [`Session`] for the context selected (`--context` or current context).
+    ///
+    /// `--endpoint` and `-s` take precedence over the endpoint of the context.
+    pub(crate) fn build(options: &GlobalOptions) -> Result<Self, Box<dyn Error>> {
+        let config = GandiCtlConfig::load(&GandiCtlConfig::path()?)?;
+        let context = config.context(options.context.as_deref())?;
+
+        let endpoint = endpoint(options, &context)?;
+
+        let personal_access_token = context
+            .token
+            .unwrap_or(TokenSource::Env(DEFAULT_TOKEN_ENV.to_owned()))
+            .token()?;
+
+        let endpoint_name = endpoint_name(&endpoint);
+        let mut api = Api::build(endpoint, personal_access_token.expose())?;
+
+        if let Some(sharing_id) = &context.sharing_id {
+            api = api.with_sharing_id(sharing_id);
+        }
+
+        if options.dry_run {
+            api = api.with_dry_run();
+        }
+
+        Ok(Session {
+            api,
+            endpoint: endpoint_name,
+            output: options.output.clone(),
+            domain: context.domain,
+            dry_run: options.dry_run,
+            yes: options.yes,
+            force: options.force,
+            protected: config.protected,
+        })
+    }
+
+    /// Print a message about a modification, flagged in dry run mode
+    pub(crate) fn notify(&self, message: &str) {
+        if self.dry_run {
+            println!("{} (dry run)", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+
+    /// Refuse to modify a protected record without `--force`
+    pub(crate) fn protect(
+        &self,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+    ) -> Result<(), String> {
+        if self.force {
+            return Ok(());
+        }
+
+        match self
+            .protected
+            .iter()
+            .any(|protected| protected.matches(fqdn, rrset_name, rrset_type))
+        {
+            true => Err(format!(
+                "Record {}.{} type {} is protected, use --force to modify it",
+                rrset_name, fqdn, rrset_type
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Ask for confirmation before overwriting or deleting an existing record.
+    ///
+    /// Protected records are refused without `--force`. There is no confirmation
+    /// with `--yes`, in dry run mode or if the record doesn't exist.
+    pub(crate) async fn confirm(
+        &self,
+        action: &str,
+        fqdn: &str,
+        rrset_name: &str,
+        rrset_type: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.protect(fqdn, rrset_name, rrset_type)?;
+
+        if self.yes || self.dry_run {
+            return Ok(());
+        }
+
+        let Some(record) = self
+            .api
+            .find_record_by_name_and_type(fqdn, rrset_name, rrset_type)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let question = format!(
+            "Record {}.{} type {} (ttl {}):\n  {}\n{} this record? [y/N] ",
+            rrset_name,
+            fqdn,
+            rrset_type,
+            record
+                .rrset_ttl
+                .map(|ttl| ttl.to_string())
+                .unwrap_or("default".to_owned()),
+            record.rrset_values.join("\n  "),
+            action,
+        );
+
+        self.ask(
+            &question,
+            &format!(
+                "{} record {}.{} type {}",
+                action, rrset_name, fqdn, rrset_type
+            ),
+        )
+    }
+
+    /// Ask a yes/no question on the terminal, an error is returned if the answer isn't yes
+    pub(crate) fn ask(&self, question: &str, subject: &str) -> Result<(), Box<dyn Error>> {
+        if !io::stdin().is_terminal() {
+            return Err(format!("{} requires a confirmation, use --yes", subject).into());
+        }
+
+        eprint!("{}", question);
+        io::stderr().flush()?;
+
+        if confirmed(io::stdin().lock())? {
+            Ok(())
+        } else {
+            Err("Aborted !".into())
+        }
+    }
+
+    /// Returns the fqdn or the default domain of the context if fqdn is '.'
+    pub(crate) fn fqdn(&self, fqdn: String) -> Result<String, String> {
+        if fqdn != "." {
+            return Ok(fqdn);
+        }
+
+        self.domain
+            .to_owned()
+            .ok_or("config: '.' requires a default domain in the context".to_owned())
+    }
+}
+
+/// Returns true if the answer is yes
+fn confirmed(mut input: impl BufRead) -> Result<bool, io::Error> {
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Endpoint of `--endpoint`, `-s` or the context (default: prod)
+pub(crate) fn endpoint(options: &GlobalOptions, context: &Context) -> Result<Endpoint, String> {
+    match (&options.endpoint, options.sandbox, &context.endpoint) {
+        (Some(endpoint), _, _) => parse_endpoint(endpoint),
+        (None, true, _) => Ok(Endpoint::Sandbox),
+        (None, false, Some(endpoint)) => parse_endpoint(endpoint),
+        (None, false, None) => Ok(Endpoint::Prod),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        Session {
+            api: Api::build(Endpoint::Prod, "token").unwrap(),
+            endpoint: "prod".to_owned(),
+            output: Output::Yaml,
+            domain: None,
+            dry_run: false,
+            yes: false,
+            force: false,
+            protected: vec![ProtectedRecord {
+                rrset_type: Some("MX".to_owned()),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn fqdn_from_context() {
+        let mut session = session();
+
+        assert_eq!(
+            session.fqdn("example.org".to_owned()).unwrap(),
+            "example.org"
+        );
+        assert!(session.fqdn(".".to_owned()).is_err());
+
+        session.domain = Some("example.net".to_owned());
+
+        assert_eq!(session.fqdn(".".to_owned()).unwrap(), "example.net");
+        assert_eq!(
+            session.fqdn("example.org".to_owned()).unwrap(),
+            "example.org"
+        );
+    }
+
+    #[test]
+    fn endpoint_precedence() {
+        let mut options = GlobalOptions {
+            sandbox: false,
+            endpoint: None,
+            context: None,
+            output: Output::Yaml,
+            dry_run: false,
+            yes: false,
+            force: false,
+        };
+        let context = Context {
+            endpoint: Some("https://api.example.net/v5".to_owned()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            endpoint(&options, &Context::default()),
+            Ok(Endpoint::Prod)
+        ));
+        assert!(matches!(
+            endpoint(&options, &context),
+            Ok(Endpoint::Custom(_))
+        ));
+
+        options.sandbox = true;
+        assert!(matches!(
+            endpoint(&options, &context),
+            Ok(Endpoint::Sandbox)
+        ));
+
+        options.endpoint = Some("prod".to_owned());
+        assert!(matches!(endpoint(&options, &context), Ok(Endpoint::Prod)));
+    }
+
+    #[test]
+    fn protect_records() {
+        let mut session = session();
+
+        assert!(session.protect("example.org", "www", "A").is_ok());
+        assert_eq!(
+            session.protect("example.org", "@", "MX").unwrap_err(),
+            "Record @.example.org type MX is protected, use --force to modify it"
+        );
+
+        session.force = true;
+
+        assert!(session.protect("example.org", "@", "MX").is_ok());
+    }
+
+    #[tokio::test]
+    async fn confirm_without_prompt() {
+        let mut session = session();
+        session.yes = true;
+
+        assert!(session
+            .confirm("Delete", "example.org", "www", "A")
+            .await
+            .is_ok());
+        assert!(session
+            .confirm("Delete", "example.org", "@", "MX")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn confirmation_answers() {
+        assert!(confirmed("y\n".as_bytes()).unwrap());
+        assert!(confirmed("YES\n".as_bytes()).unwrap());
+        assert!(!confirmed("\n".as_bytes()).unwrap());
+        assert!(!confirmed("no\n".as_bytes()).unwrap());
+    }
+}
@@ -0,0 +1,352 @@
+use gandi_v5_livedns_api::records::Record;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    changes::Change, cli::RRSET_TTL_RANGE, config::state_directory, selection::Selection,
+    session::Session,
+};
+
+/// Ttl of records without an explicit ttl
+const DEFAULT_TTL: u32 = 10800;
+
+/// Ttls of records before a `ttl set`, saved to be restored
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct State {
+    fqdn: String,
+    records: Vec<SavedTtl>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct SavedTtl {
+    rrset_name: String,
+    rrset_type: String,
+    rrset_ttl: Option<u32>,
+}
+
+/// Set the ttl of the selected records which have another ttl
+///
+/// The previous ttls are saved in a state file (`state` or `$XDG_STATE_HOME/gandictl/ttl/{fqdn}.yaml`).
+pub(crate) async fn set(
+    session: &Session,
+    fqdn: String,
+    rrset_ttl: u32,
+    selection: Selection,
+    only_above: Option<u32>,
+    state: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let fqdn = session.fqdn(fqdn)?;
+    let path = state_path(state, &fqdn)?;
+    let records = selection.filter(session.api.records(&fqdn).await?);
+
+    let changes = set_changes(&records, rrset_ttl, only_above);
+
+    if changes.is_empty() {
+        println!("Nothing to change !");
+        return Ok(());
+    }
+
+    confirm(session, &fqdn, &changes, "Set ttl")?;
+
+    if !session.dry_run {
+        let mut state = State::load(&path, &fqdn)?;
+        state.save_previous(&changes);
+        state.save(&path)?;
+    }
+
+    apply(session, &fqdn, &changes).await?;
+
+    session.notify(&format!(
+        "Ttl of {} records set to {} on {} !",
+        changes.len(),
+        rrset_ttl,
+        fqdn
+    ));
+
+    Ok(())
+}
+
+/// Reapply the ttls saved by the previous `ttl set` and remove the state file
+pub(crate) async fn restore(
+    session: &Session,
+    fqdn: String,
+    state: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let fqdn = session.fqdn(fqdn)?;
+    let path = state_path(state, &fqdn)?;
+
+    if !path.exists() {
+        return Err(format!("ttl: no saved ttl ({})", path.display()).into());
+    }
+
+    let state = State::load(&path, &fqdn)?;
+    let records = session.api.records(&fqdn).await?;
+
+    let changes = state.restore_changes(&records)?;
+
+    if !changes.is_empty() {
+        confirm(session, &fqdn, &changes, "Restore ttl")?;
+        apply(session, &fqdn, &changes).await?;
+    }
+
+    if !session.dry_run {
+        fs::remove_file(&path)?;
+    }
+
+    session.notify(&format!(
+        "Ttl of {} records restored on {} !",
+        changes.len(),
+        fqdn
+    ));
+
+    Ok(())
+}
+
+fn state_path(state: Option<PathBuf>, fqdn: &str) -> Result<PathBuf, Box<dyn Error>> {
+    match state {
+        Some(path) => Ok(path),
+        None => Ok(state_directory()?
+            .join("ttl")
+            .join(format!("{}.yaml", fqdn))),
+    }
+}
+
+/// Print the changes and ask for a confirmation
+fn confirm(
+    session: &Session,
+    fqdn: &str,
+    changes: &[Change],
+    action: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("{}:", fqdn);
+
+    for change in changes {
+        let record = change.record();
+        session.protect(fqdn, &record.rrset_name, &record.rrset_type)?;
+
+        print!("{}", change);
+    }
+
+    if !session.yes && !session.dry_run {
+        session.ask(
+            &format!("{} of {} records? [y/N] ", action, changes.len()),
+            action,
+        )?;
+    }
+
+    Ok(())
+}
+
+async fn apply(session: &Session, fqdn: &str, changes: &[Change]) -> Result<(), Box<dyn Error>> {
+    let mut errors = vec![];
+
+    for change in changes {
+        if let Err(e) = change.apply(session, fqdn).await {
+            let record = change.record();
+            errors.push(format!(
+                "ttl: {} ({} {})",
+                e, record.rrset_name, record.rrset_type
+            ));
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("\n").into()),
+    }
+}
+
+/// Updates of the records with another ttl (only above a ttl with `only_above`)
+fn set_changes(records: &[Record], rrset_ttl: u32, only_above: Option<u32>) -> Vec<Change> {
+    records
+        .iter()
+        .filter(|record| record.rrset_ttl != Some(rrset_ttl))
+        .filter(|record| only_above.is_none_or(|ttl| record.rrset_ttl.unwrap_or(DEFAULT_TTL) > ttl))
+        .map(|record| Change::Update {
+            before: record.to_owned(),
+            after: Record {
+                rrset_ttl: Some(rrset_ttl),
+                ..record.to_owned()
+            },
+        })
+        .collect()
+}
+
+impl State {
+    fn load(path: &Path, fqdn: &str) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(State {
+                fqdn: fqdn.to_owned(),
+                ..Default::default()
+            });
+        }
+
+        let state: State = serde_yaml::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| format!("ttl: {} ({})", e, path.display()))?;
+
+        if state.fqdn != fqdn {
+            return Err(format!("ttl: {} holds ttls of {}", path.display(), state.fqdn).into());
+        }
+
+        Ok(state)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_yaml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// Keep the ttls before the changes, ttls saved by a previous run aren't overwritten
+    fn save_previous(&mut self, changes: &[Change]) {
+        for change in changes {
+            let Change::Update { before, .. } = change else {
+                continue;
+            };
+
+            if !self.records.iter().any(|saved| {
+                saved.rrset_name == before.rrset_name && saved.rrset_type == before.rrset_type
+            }) {
+                self.records.push(SavedTtl {
+                    rrset_name: before.rrset_name.to_owned(),
+                    rrset_type: before.rrset_type.to_owned(),
+                    rrset_ttl: before.rrset_ttl,
+                });
+            }
+        }
+    }
+
+    /// Updates of the existing records to their saved ttl
+    fn restore_changes(&self, records: &[Record]) -> Result<Vec<Change>, Box<dyn Error>> {
+        let mut changes = vec![];
+
+        for saved in &self.records {
+            if let Some(rrset_ttl) = saved.rrset_ttl {
+                if !RRSET_TTL_RANGE.contains(&(rrset_ttl as usize)) {
+                    return Err(format!(
+                        "ttl: saved ttl {} of {} {} not in range {}-{}",
+                        rrset_ttl,
+                        saved.rrset_name,
+                        saved.rrset_type,
+                        RRSET_TTL_RANGE.start(),
+                        RRSET_TTL_RANGE.end()
+                    )
+                    .into());
+                }
+            }
+
+            let Some(record) = records.iter().find(|record| {
+                record.rrset_name == saved.rrset_name && record.rrset_type == saved.rrset_type
+            }) else {
+                continue;
+            };
+
+            if record.rrset_ttl != saved.rrset_ttl {
+                changes.push(Change::Update {
+                    before: record.to_owned(),
+                    after: Record {
+                        rrset_ttl: saved.rrset_ttl,
+                        ..record.to_owned()
+                    },
+                });
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    fn with_ttl(rrset_name: &str, rrset_type: &str, rrset_ttl: Option<u32>) -> Record {
+        Record {
+            rrset_ttl,
+            ..record(rrset_name, rrset_type, &["10.0.0.1"])
+        }
+    }
+
+    #[test]
+    fn set_only_different_ttls() {
+        let records = vec![
+            with_ttl("www", "A", Some(300)),
+            with_ttl("mail", "A", Some(3600)),
+            with_ttl("ftp", "A", None),
+        ];
+
+        let changes = set_changes(&records, 300, None);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].record(), &with_ttl("ftp", "A", Some(300)));
+
+        let changes = set_changes(&records, 300, Some(3600));
+
+        assert_eq!(
+            changes,
+            vec![Change::Update {
+                before: with_ttl("ftp", "A", None),
+                after: with_ttl("ftp", "A", Some(300)),
+            }]
+        );
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let records = vec![with_ttl("www", "A", Some(3600)), with_ttl("ftp", "A", None)];
+
+        let mut state = State {
+            fqdn: "example.org".to_owned(),
+            ..Default::default()
+        };
+        state.save_previous(&set_changes(&records, 300, None));
+
+        let lowered = vec![
+            with_ttl("www", "A", Some(300)),
+            with_ttl("ftp", "A", Some(300)),
+        ];
+
+        // a second run keeps the original ttls
+        state.save_previous(&set_changes(&lowered, 600, None));
+
+        assert_eq!(
+            state.restore_changes(&lowered).unwrap(),
+            vec![
+                Change::Update {
+                    before: with_ttl("www", "A", Some(300)),
+                    after: with_ttl("www", "A", Some(3600)),
+                },
+                Change::Update {
+                    before: with_ttl("ftp", "A", Some(300)),
+                    after: with_ttl("ftp", "A", None),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn restore_out_of_range() {
+        let state = State {
+            fqdn: "example.org".to_owned(),
+            records: vec![SavedTtl {
+                rrset_name: "www".to_owned(),
+                rrset_type: "A".to_owned(),
+                rrset_ttl: Some(60),
+            }],
+        };
+
+        assert_eq!(
+            state.restore_changes(&[]).unwrap_err().to_string(),
+            "ttl: saved ttl 60 of www A not in range 300-2592000"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use gandi_v5_livedns_api::records::Record;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    changes::{diff, Change},
+    output::Output,
+    session::Session,
+};
+
+/// Kind of modification of a rrset
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Modification of a rrset seen between two polls
+#[derive(Debug, PartialEq, Serialize)]
+struct Event {
+    /// Date of the poll (RFC 3339)
+    time: String,
+    fqdn: String,
+    event: Kind,
+    rrset_name: String,
+    rrset_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    before: Option<Record>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after: Option<Record>,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Added => write!(f, "added"),
+            Kind::Removed => write!(f, "removed"),
+            Kind::Modified => write!(f, "modified"),
+        }
+    }
+}
+
+impl Event {
+    fn new(time: &str, fqdn: &str, change: Change) -> Self {
+        let rrset_name = change.record().rrset_name.to_owned();
+        let rrset_type = change.record().rrset_type.to_owned();
+        let (event, before, after) = match change {
+            Change::Create(record) => (Kind::Added, None, Some(record)),
+            Change::Update { before, after } => (Kind::Modified, Some(before), Some(after)),
+            Change::Delete(record) => (Kind::Removed, Some(record), None),
+        };
+
+        Event {
+            time: time.to_owned(),
+            fqdn: fqdn.to_owned(),
+            event,
+            rrset_name,
+            rrset_type,
+            before,
+            after,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = |record: &Record| {
+            format!(
+                "{} {}",
+                record
+                    .rrset_ttl
+                    .map(|ttl| ttl.to_string())
+                    .unwrap_or("default".to_owned()),
+                record.rrset_values.join(",")
+            )
+        };
+
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.time, self.fqdn, self.event, self.rrset_name, self.rrset_type
+        )?;
+
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => write!(f, " {} -> {}", state(before), state(after)),
+            (Some(record), None) | (None, Some(record)) => write!(f, " {}", state(record)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Poll the records of a domain and print added, removed or modified rrsets until Ctrl+C
+///
+/// Events are printed as JSON lines with the json output, human readable otherwise.
+pub(crate) async fn watch(
+    session: &Session,
+    fqdn: &str,
+    rrset_name: Option<&str>,
+    interval: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let fetch = || async {
+        match rrset_name {
+            Some(rrset_name) => session.api.records_by_name(fqdn, rrset_name).await,
+            None => session.api.records(fqdn).await,
+        }
+    };
+
+    let mut previous = fetch().await?;
+
+    eprintln!(
+        "Watching {} every {} (Ctrl+C to stop)",
+        fqdn,
+        humantime::format_duration(interval)
+    );
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        let current = match fetch().await {
+            Ok(current) => current,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        let time = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+
+        for event in events(&time, fqdn, &previous, &current) {
+            match session.output {
+                Output::Json => println!("{}", serde_json::to_string(&event)?),
+                _ => println!("{}", event),
+            }
+        }
+
+        previous = current;
+    }
+}
+
+fn events(time: &str, fqdn: &str, previous: &[Record], current: &[Record]) -> Vec<Event> {
+    diff(previous, current, true)
+        .into_iter()
+        .map(|change| Event::new(time, fqdn, change))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    #[test]
+    fn events_between_polls() {
+        let previous = vec![
+            record("www", "A", &["10.0.0.1"]),
+            record("old", "CNAME", &["www"]),
+        ];
+        let current = vec![
+            record("www", "A", &["10.0.0.2"]),
+            record("new", "A", &["10.0.0.3"]),
+        ];
+
+        let events = events("2024-03-20T10:00:00Z", "example.org", &previous, &current);
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "2024-03-20T10:00:00Z example.org added new A 300 10.0.0.3",
+                "2024-03-20T10:00:00Z example.org modified www A 300 10.0.0.1 -> 300 10.0.0.2",
+                "2024-03-20T10:00:00Z example.org removed old CNAME 300 www",
+            ]
+        );
+    }
+
+    #[test]
+    fn json_event() {
+        let event = Event::new(
+            "2024-03-20T10:00:00Z",
+            "example.org",
+            Change::Delete(record("old", "CNAME", &["www"])),
+        );
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"time":"2024-03-20T10:00:00Z","fqdn":"example.org","event":"removed","rrset_name":"old","rrset_type":"CNAME","before":{"rrset_name":"old","rrset_type":"CNAME","rrset_values":["www"],"rrset_ttl":300}}"#
+        );
+    }
+}